@@ -1,12 +1,9 @@
 use assert_no_alloc::*;
 use bevy_daw::nodes::{DelayNode, DistortionNode, DistortionType, GainNode, ToneGeneratorNode};
 use bevy_daw::traits::AudioNode;
+use bevy_daw::{AudioBufferMut, ProcessContext};
 use criterion::{Bencher, Criterion, criterion_group, criterion_main};
 
-#[cfg(debug_assertions)]
-#[global_allocator]
-static A: AllocDisabler = AllocDisabler;
-
 const BUFFER_SIZE: usize = 4096;
 
 macro_rules! bench_nodes_group {
@@ -19,10 +16,13 @@ macro_rules! bench_nodes_group {
         $(
             fn $bench_name(c: &mut Criterion) {
                 let mut buffer = [0.0f32; BUFFER_SIZE];
+                let mut output = AudioBufferMut::new(&mut buffer, 2, BUFFER_SIZE / 2);
                 let mut node = <$node_type>::new($($ctor_arg),*);
+                node.prepare(44_100, BUFFER_SIZE / 2, 2);
+                let ctx = ProcessContext::default();
 
                 c.bench_function(stringify!($bench_name), |b: &mut Bencher| {
-                    b.iter(|| assert_no_alloc(|| node.process(0, &mut buffer)))
+                    b.iter(|| assert_no_alloc(|| node.process(&ctx, &mut output)))
                 });
             }
         )*
@@ -33,7 +33,7 @@ macro_rules! bench_nodes_group {
 }
 
 bench_nodes_group!(benches, [
-    tone_generator_bench => (ToneGeneratorNode, 440.0, 0.5),
+    tone_generator_bench => (ToneGeneratorNode, 440.0_f32, 0.5),
     delay_generator_bench => (DelayNode, 11025),
     dist_soft_clip_bench => (DistortionNode,4.0,0.5,DistortionType::SoftClip),
     dist_hard_clip_bench => (DistortionNode,4.0,0.5,DistortionType::HardClip),
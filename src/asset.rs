@@ -0,0 +1,212 @@
+use crate::error::DawError;
+use bevy::asset::io::Reader;
+use bevy::asset::{Asset, AssetLoader, LoadContext};
+use bevy::reflect::TypePath;
+use std::io::{Cursor, Read};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Decoded, interleaved audio. Cloning shares the samples, so any number of
+// players can use the same clip.
+#[derive(Asset, Clone, Debug, TypePath)]
+pub struct AudioClip {
+    sample_rate: u32,
+    channels: usize,
+    samples: Arc<[f32]>,
+}
+
+impl AudioClip {
+    pub fn new(samples: impl Into<Arc<[f32]>>, sample_rate: u32, channels: usize) -> Self {
+        let channels = channels.max(1);
+        let samples: Arc<[f32]> = samples.into();
+
+        // Drop a trailing partial frame.
+        let len = samples.len() / channels * channels;
+        let samples = if len == samples.len() {
+            samples
+        } else {
+            samples[..len].into()
+        };
+
+        Self {
+            sample_rate,
+            channels,
+            samples,
+        }
+    }
+
+    // Decodes 8 to 32 bit integer or 32 bit float WAV data and converts it to
+    // `sample_rate` and `channels`.
+    pub fn from_wav(
+        reader: impl Read,
+        sample_rate: u32,
+        channels: usize,
+    ) -> Result<Self, DawError> {
+        let reader = hound::WavReader::new(reader)?;
+        let spec = reader.spec();
+
+        let samples: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<_, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .into_samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 * scale))
+                    .collect::<Result<_, _>>()?
+            }
+        };
+
+        let clip = Self::new(samples, spec.sample_rate, spec.channels as usize);
+        Ok(clip.converted(sample_rate, channels))
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frames() as f64 / self.sample_rate.max(1) as f64)
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    // Zero past the end.
+    pub fn sample(&self, frame: usize, channel: usize) -> f32 {
+        self.samples
+            .get(frame * self.channels + channel % self.channels)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    // Linear resampling. Mono is copied to every channel, anything going to
+    // mono is averaged, otherwise channels wrap around.
+    pub fn converted(&self, sample_rate: u32, channels: usize) -> Self {
+        let channels = channels.max(1);
+
+        if sample_rate == self.sample_rate && channels == self.channels {
+            return self.clone();
+        }
+
+        let step = self.sample_rate as f64 / sample_rate as f64;
+        let frames = (self.frames() as f64 / step).round() as usize;
+        let mut samples = Vec::with_capacity(frames * channels);
+
+        for frame in 0..frames {
+            let pos = frame as f64 * step;
+            let (index, frac) = (pos as usize, pos.fract() as f32);
+            let at = |ch| {
+                let a = self.sample(index, ch);
+                a + (self.sample(index + 1, ch) - a) * frac
+            };
+
+            for ch in 0..channels {
+                let sample = if channels == 1 {
+                    (0..self.channels).map(at).sum::<f32>() / self.channels as f32
+                } else {
+                    at(ch)
+                };
+
+                samples.push(sample);
+            }
+        }
+
+        Self::new(samples, sample_rate, channels)
+    }
+}
+
+// Registered by `DawPlugin` with the controller's format.
+#[derive(Debug)]
+pub(crate) struct AudioClipLoader {
+    pub(crate) sample_rate: u32,
+    pub(crate) channels: usize,
+}
+
+impl AssetLoader for AudioClipLoader {
+    type Asset = AudioClip;
+    type Settings = ();
+    type Error = DawError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<AudioClip, DawError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(hound::Error::from)?;
+
+        AudioClip::from_wav(Cursor::new(bytes), self.sample_rate, self.channels)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["wav"]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AudioClip;
+    use std::io::Cursor;
+
+    fn wav(
+        spec: hound::WavSpec,
+        write: impl Fn(&mut hound::WavWriter<&mut Cursor<Vec<u8>>>),
+    ) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut cursor, spec).unwrap();
+        write(&mut writer);
+        writer.finalize().unwrap();
+        cursor.into_inner()
+    }
+
+    #[test]
+    fn wav_formats_decode_to_the_engine_format() {
+        for bits in [8, 16, 24, 32] {
+            let spec = hound::WavSpec {
+                channels: 1,
+                sample_rate: 1000,
+                bits_per_sample: bits,
+                sample_format: hound::SampleFormat::Int,
+            };
+            let half = 1i32 << (bits - 2);
+            let data = wav(spec, |w| {
+                [half, -half]
+                    .iter()
+                    .for_each(|s| w.write_sample(*s).unwrap())
+            });
+
+            let clip = AudioClip::from_wav(Cursor::new(data), 1000, 2).unwrap();
+            assert_eq!(clip.samples(), &[0.5, 0.5, -0.5, -0.5], "{bits} bit");
+        }
+
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 1000,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let data = wav(spec, |w| {
+            [0.0, 1.0, 1.0, 0.0]
+                .iter()
+                .for_each(|s| w.write_sample(*s).unwrap())
+        });
+
+        let clip = AudioClip::from_wav(Cursor::new(data), 2000, 1).unwrap();
+        assert_eq!(clip.sample_rate(), 2000);
+        assert_eq!(clip.frames(), 4);
+        assert_eq!(clip.samples(), &[0.5, 0.5, 0.5, 0.25]);
+    }
+}
@@ -0,0 +1,209 @@
+use std::ops::Range;
+
+#[derive(Clone, Debug, Default)]
+pub struct AudioBuffer {
+    data: Vec<f32>,
+    channels: usize,
+    frames: usize,
+}
+
+impl AudioBuffer {
+    pub fn new(channels: usize, frames: usize) -> Self {
+        Self {
+            data: vec![0.0; channels * frames],
+            channels,
+            frames,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn channel(&self, channel: usize) -> &[f32] {
+        &self.data[channel * self.frames..(channel + 1) * self.frames]
+    }
+
+    pub fn as_ref(&self) -> AudioBufferRef<'_> {
+        self.slice(0..self.frames)
+    }
+
+    pub fn as_mut(&mut self) -> AudioBufferMut<'_> {
+        self.slice_mut(0..self.frames)
+    }
+
+    pub fn slice(&self, frames: Range<usize>) -> AudioBufferRef<'_> {
+        AudioBufferRef {
+            data: &self.data[frames.start..],
+            channels: self.channels,
+            frames: frames.len(),
+            stride: self.frames,
+        }
+    }
+
+    pub fn slice_mut(&mut self, frames: Range<usize>) -> AudioBufferMut<'_> {
+        AudioBufferMut {
+            data: &mut self.data[frames.start..],
+            channels: self.channels,
+            frames: frames.len(),
+            stride: self.frames,
+        }
+    }
+}
+
+// Planar views, channel `n` starts at `n * stride`.
+#[derive(Clone, Copy, Debug)]
+pub struct AudioBufferRef<'a> {
+    data: &'a [f32],
+    channels: usize,
+    frames: usize,
+    stride: usize,
+}
+
+impl AudioBufferRef<'_> {
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn channel(&self, channel: usize) -> &[f32] {
+        let start = channel * self.stride;
+        &self.data[start..start + self.frames]
+    }
+}
+
+#[derive(Debug)]
+pub struct AudioBufferMut<'a> {
+    data: &'a mut [f32],
+    channels: usize,
+    frames: usize,
+    stride: usize,
+}
+
+impl<'a> AudioBufferMut<'a> {
+    pub fn new(data: &'a mut [f32], channels: usize, frames: usize) -> Self {
+        assert!(data.len() >= channels * frames);
+
+        Self {
+            data,
+            channels,
+            frames,
+            stride: frames,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn channel(&self, channel: usize) -> &[f32] {
+        let start = channel * self.stride;
+        &self.data[start..start + self.frames]
+    }
+
+    pub fn channel_mut(&mut self, channel: usize) -> &mut [f32] {
+        let start = channel * self.stride;
+        &mut self.data[start..start + self.frames]
+    }
+
+    pub fn channels_mut(&mut self) -> impl Iterator<Item = &mut [f32]> {
+        let frames = self.frames;
+
+        self.data
+            .chunks_mut(self.stride)
+            .take(self.channels)
+            .map(move |channel| &mut channel[..frames])
+    }
+
+    pub fn as_ref(&self) -> AudioBufferRef<'_> {
+        AudioBufferRef {
+            data: self.data,
+            channels: self.channels,
+            frames: self.frames,
+            stride: self.stride,
+        }
+    }
+
+    pub fn reborrow(&mut self) -> AudioBufferMut<'_> {
+        self.slice_mut(0..self.frames)
+    }
+
+    pub fn slice_mut(&mut self, frames: Range<usize>) -> AudioBufferMut<'_> {
+        assert!(frames.end <= self.frames);
+
+        AudioBufferMut {
+            data: &mut self.data[frames.start..],
+            channels: self.channels,
+            frames: frames.len(),
+            stride: self.stride,
+        }
+    }
+
+    pub fn fill(&mut self, value: f32) {
+        for channel in self.channels_mut() {
+            channel.fill(value);
+        }
+    }
+
+    // Mono sources are spread over every channel.
+    pub fn add_from(&mut self, other: &AudioBufferRef) {
+        let frames = self.frames.min(other.frames);
+
+        for ch in 0..self.channels {
+            let src = if other.channels == 1 {
+                other.channel(0)
+            } else if ch < other.channels {
+                other.channel(ch)
+            } else {
+                continue;
+            };
+
+            for (dst, src) in self.channel_mut(ch)[..frames].iter_mut().zip(src) {
+                *dst += *src;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AudioBuffer, AudioBufferMut};
+
+    #[test]
+    fn slices_keep_channel_layout() {
+        let mut buffer = AudioBuffer::new(2, 8);
+
+        {
+            let mut view = buffer.slice_mut(4..8);
+            view.channel_mut(0).fill(1.0);
+            view.channel_mut(1).fill(2.0);
+            assert_eq!(view.frames(), 4);
+        }
+
+        assert_eq!(buffer.channel(0), &[0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(buffer.channel(1), &[0.0, 0.0, 0.0, 0.0, 2.0, 2.0, 2.0, 2.0]);
+    }
+
+    #[test]
+    fn mono_is_spread_over_channels() {
+        let mut mono = [0.5; 4];
+        let mono = AudioBufferMut::new(&mut mono, 1, 4);
+
+        let mut stereo = AudioBuffer::new(2, 4);
+        stereo.as_mut().add_from(&mono.as_ref());
+
+        assert_eq!(stereo.channel(0), &[0.5; 4]);
+        assert_eq!(stereo.channel(1), &[0.5; 4]);
+    }
+}
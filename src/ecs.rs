@@ -0,0 +1,282 @@
+use crate::engine::{AudioController, AudioNodeEvent};
+use crate::node::{AudioNode, NodeEvent, NodeId};
+use bevy::ecs::component::Component;
+use bevy::ecs::entity::Entity;
+use bevy::ecs::event::EventReader;
+use bevy::ecs::hierarchy::{ChildOf, Children};
+use bevy::ecs::observer::Trigger;
+use bevy::ecs::query::Has;
+use bevy::ecs::system::{Commands, Query, ResMut};
+use bevy::ecs::world::OnReplace;
+
+mod spatial;
+
+pub(crate) use spatial::spatialize;
+pub use spatial::{Attenuation, AudioEmitter, AudioListener, Cone};
+
+// Spawning adds the node to the engine, despawning removes it and inserting
+// another one swaps it. The output goes into the nearest ancestor entity with a
+// node of its own, or into the master.
+#[derive(Component, Debug)]
+pub struct DawNode(Option<Box<dyn AudioNode>>);
+
+impl DawNode {
+    pub fn new(node: impl AudioNode + 'static) -> Self {
+        Self(Some(Box::new(node)))
+    }
+}
+
+impl From<Box<dyn AudioNode>> for DawNode {
+    fn from(node: Box<dyn AudioNode>) -> Self {
+        Self(Some(node))
+    }
+}
+
+// Inserted once the node made it into the engine.
+#[derive(Clone, Copy, Component, Debug)]
+pub struct DawNodeId(NodeId);
+
+impl DawNodeId {
+    pub fn id(&self) -> NodeId {
+        self.0
+    }
+}
+
+// Despawns the entity, children included, once its node finished. Without it
+// only the node components are removed, from the children as well.
+#[derive(Clone, Copy, Component, Debug, Default)]
+pub struct DespawnOnFinish;
+
+// Where the output is connected right now.
+#[derive(Component, Debug)]
+pub(crate) struct Routed(NodeId);
+
+pub(crate) fn add_nodes(
+    mut controller: ResMut<AudioController>,
+    mut query: Query<(Entity, &mut DawNode)>,
+    mut commands: Commands,
+) {
+    for (entity, mut node) in &mut query {
+        let Some(node) = node.0.take() else {
+            continue;
+        };
+
+        // A refused node is dropped, the entity just stays silent.
+        if let Ok(id) = controller.add_node(node) {
+            commands.entity(entity).insert(DawNodeId(id));
+        }
+    }
+}
+
+// Connections that fail are retried on the next run.
+pub(crate) fn route_nodes(
+    mut controller: ResMut<AudioController>,
+    nodes: Query<(Entity, &DawNodeId, Option<&Routed>)>,
+    parents: Query<&ChildOf>,
+    mut commands: Commands,
+) {
+    for (entity, id, routed) in &nodes {
+        let target = parents
+            .iter_ancestors(entity)
+            .find_map(|ancestor| nodes.get(ancestor).ok())
+            .map_or(NodeId::MASTER, |(_, parent, _)| parent.0);
+
+        if let Some(routed) = routed {
+            if routed.0 == target {
+                continue;
+            }
+
+            controller.disconnect(id.0, routed.0).ok();
+        }
+
+        if controller.connect(id.0, target).is_ok() {
+            commands.entity(entity).insert(Routed(target));
+        }
+    }
+}
+
+pub(crate) fn finish_nodes(
+    mut events: EventReader<AudioNodeEvent>,
+    query: Query<(Entity, &DawNodeId, Has<DespawnOnFinish>)>,
+    children: Query<&Children>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        if event.event != NodeEvent::Finished {
+            continue;
+        }
+
+        let Some((entity, _, despawn)) = query.iter().find(|(_, id, _)| id.0 == event.node) else {
+            continue;
+        };
+
+        if despawn {
+            commands.entity(entity).despawn();
+        } else {
+            // Rerouted to the master, the children would play on forever.
+            for entity in [entity]
+                .into_iter()
+                .chain(children.iter_descendants(entity))
+            {
+                commands.entity(entity).remove::<DawNode>();
+            }
+        }
+    }
+}
+
+pub(crate) fn remove_node(
+    trigger: Trigger<OnReplace, DawNode>,
+    query: Query<&DawNodeId>,
+    mut controller: ResMut<AudioController>,
+    mut commands: Commands,
+) {
+    let entity = trigger.target();
+
+    if let Ok(id) = query.get(entity) {
+        controller.remove_node(id.0).ok();
+        commands.entity(entity).try_remove::<(DawNodeId, Routed)>();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        DawNode, DawNodeId, DespawnOnFinish, add_nodes, finish_nodes, remove_node, route_nodes,
+    };
+    use crate::engine::{AudioController, AudioNodeEvent, DEFAULT_SAMPLE_RATE};
+    use crate::node::NodeEvent;
+    use crate::node::nodes::{GainNode, ToneGeneratorNode};
+    use bevy::app::{App, Update};
+    use bevy::ecs::hierarchy::ChildOf;
+    use bevy::ecs::schedule::IntoScheduleConfigs;
+
+    #[test]
+    fn entities_add_route_and_remove_nodes() {
+        let (controller, mut renderer) = AudioController::offline(DEFAULT_SAMPLE_RATE, 1);
+
+        let mut app = App::new();
+        app.insert_resource(controller)
+            .add_systems(Update, (add_nodes, route_nodes).chain())
+            .add_observer(remove_node);
+
+        // Muted by its parent.
+        let parent = app
+            .world_mut()
+            .spawn(DawNode::new(GainNode::new(0.0)))
+            .with_child(DawNode::new(ToneGeneratorNode::new(440.0_f32, 1.0)))
+            .id();
+        app.update();
+
+        assert!(renderer.render_frames(512).iter().all(|s| *s == 0.0));
+
+        app.world_mut().despawn(parent);
+        let tone = app
+            .world_mut()
+            .spawn(DawNode::new(ToneGeneratorNode::new(440.0_f32, 1.0)))
+            .id();
+        app.update();
+
+        assert!(renderer.render_frames(512).iter().any(|s| *s != 0.0));
+
+        app.world_mut().despawn(tone);
+        assert!(renderer.render_frames(512).iter().all(|s| *s == 0.0));
+
+        // Only the master is left.
+        let controller = app.world().resource::<AudioController>();
+        assert_eq!(controller.profile().len(), 1);
+    }
+
+    #[test]
+    fn replaced_nodes_are_swapped_in_the_engine() {
+        let (controller, mut renderer) = AudioController::offline(DEFAULT_SAMPLE_RATE, 1);
+
+        let mut app = App::new();
+        app.insert_resource(controller)
+            .add_systems(Update, (add_nodes, route_nodes).chain())
+            .add_observer(remove_node);
+
+        let entity = app
+            .world_mut()
+            .spawn(DawNode::new(ToneGeneratorNode::new(440.0_f32, 1.0)))
+            .id();
+        app.update();
+        let tone = app.world().get::<DawNodeId>(entity).unwrap().id();
+
+        app.world_mut()
+            .entity_mut(entity)
+            .insert(DawNode::new(GainNode::new(0.0)));
+        app.update();
+
+        assert_ne!(app.world().get::<DawNodeId>(entity).unwrap().id(), tone);
+        assert!(renderer.render_frames(512).iter().all(|s| *s == 0.0));
+
+        let controller = app.world().resource::<AudioController>();
+        assert_eq!(controller.profile().len(), 2);
+    }
+
+    #[test]
+    fn finished_nodes_clean_up_their_entities() {
+        let (controller, _renderer) = AudioController::offline(DEFAULT_SAMPLE_RATE, 1);
+
+        let mut app = App::new();
+        app.insert_resource(controller)
+            .add_event::<AudioNodeEvent>()
+            .add_systems(Update, (finish_nodes, add_nodes).chain())
+            .add_observer(remove_node);
+
+        let kept = app.world_mut().spawn(DawNode::new(GainNode::new(1.0))).id();
+        let despawned = app
+            .world_mut()
+            .spawn((DawNode::new(GainNode::new(1.0)), DespawnOnFinish))
+            .id();
+        app.update();
+
+        for entity in [kept, despawned] {
+            let node = app.world().get::<DawNodeId>(entity).unwrap().id();
+            app.world_mut().send_event(AudioNodeEvent {
+                node,
+                event: NodeEvent::Finished,
+            });
+        }
+        app.update();
+
+        assert!(app.world().get::<DawNode>(kept).is_none());
+        assert!(app.world().get::<DawNodeId>(kept).is_none());
+        assert!(app.world().get_entity(despawned).is_err());
+    }
+
+    #[test]
+    fn finished_nodes_take_their_children_along() {
+        let (controller, mut renderer) = AudioController::offline(DEFAULT_SAMPLE_RATE, 1);
+
+        let mut app = App::new();
+        app.insert_resource(controller)
+            .add_event::<AudioNodeEvent>()
+            .add_systems(Update, (finish_nodes, add_nodes, route_nodes).chain())
+            .add_observer(remove_node);
+
+        let parent = app.world_mut().spawn(DawNode::new(GainNode::new(1.0))).id();
+        let child = app
+            .world_mut()
+            .spawn((
+                DawNode::new(ToneGeneratorNode::new(440.0_f32, 1.0)),
+                ChildOf(parent),
+            ))
+            .id();
+        app.update();
+
+        let node = app.world().get::<DawNodeId>(parent).unwrap().id();
+        app.world_mut().send_event(AudioNodeEvent {
+            node,
+            event: NodeEvent::Finished,
+        });
+        app.update();
+
+        assert!(app.world().get::<DawNode>(child).is_none());
+        assert!(app.world().get::<DawNodeId>(child).is_none());
+        assert!(renderer.render_frames(512).iter().all(|s| *s == 0.0));
+
+        let controller = app.world().resource::<AudioController>();
+        assert_eq!(controller.profile().len(), 1);
+    }
+}
@@ -0,0 +1,256 @@
+use super::{DawNode, DawNodeId};
+use crate::engine::AudioController;
+use crate::node::nodes::SpatialNode;
+use crate::node::param::ParamId;
+use bevy::ecs::change_detection::{DetectChanges, Ref};
+use bevy::ecs::component::Component;
+use bevy::ecs::query::With;
+use bevy::ecs::system::{Query, Res};
+use bevy::math::Vec3;
+use bevy::transform::components::{GlobalTransform, Transform};
+
+const SPEED_OF_SOUND: f32 = 343.0;
+
+const PARAMS: [ParamId; 3] = [SpatialNode::GAIN, SpatialNode::PAN, SpatialNode::DELAY];
+// Anything smaller isn't worth a command, the delay is in seconds.
+const EPSILONS: [f32; 3] = [1e-3, 1e-3, 1e-5];
+
+// Only the first listener is used.
+#[derive(Clone, Copy, Component, Debug, Default)]
+#[require(Transform)]
+pub struct AudioListener;
+
+// Distance models as in OpenAL, the distance is clamped between the
+// emitter's `min_distance` and `max_distance`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Attenuation {
+    #[default]
+    Inverse,
+    Linear,
+    Exponential,
+}
+
+// Full angles in radians around the emitter's forward direction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cone {
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    pub outer_gain: f32,
+}
+
+// The entity's node is a `SpatialNode`, the sounds to place are spawned as its
+// children so they are routed through it.
+#[derive(Clone, Copy, Component, Debug, PartialEq)]
+#[require(Transform, DawNode = DawNode::new(SpatialNode::new()), Placement)]
+pub struct AudioEmitter {
+    pub attenuation: Attenuation,
+    pub rolloff: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub cone: Option<Cone>,
+    pub doppler: bool,
+}
+
+// Gain, pan and delay as last sent to the emitter's node.
+#[derive(Clone, Copy, Component, Debug, Default)]
+pub(crate) struct Placement([Option<f32>; 3]);
+
+impl Default for AudioEmitter {
+    fn default() -> Self {
+        Self {
+            attenuation: Attenuation::Inverse,
+            rolloff: 1.0,
+            min_distance: 1.0,
+            max_distance: 100.0,
+            cone: None,
+            doppler: false,
+        }
+    }
+}
+
+impl AudioEmitter {
+    fn distance_gain(&self, distance: f32) -> f32 {
+        let min = self.min_distance.max(f32::EPSILON);
+        let max = self.max_distance.max(min);
+        let distance = distance.clamp(min, max);
+
+        let gain = match self.attenuation {
+            Attenuation::Inverse => min / (min + self.rolloff * (distance - min)),
+            Attenuation::Linear if max > min => 1.0 - self.rolloff * (distance - min) / (max - min),
+            Attenuation::Linear => 1.0,
+            Attenuation::Exponential => (distance / min).powf(-self.rolloff),
+        };
+
+        gain.clamp(0.0, 1.0)
+    }
+
+    // `to_listener` is normalized, or zero if both are in the same spot.
+    fn cone_gain(&self, forward: Vec3, to_listener: Vec3) -> f32 {
+        let Some(cone) = self.cone else {
+            return 1.0;
+        };
+
+        if to_listener == Vec3::ZERO {
+            return 1.0;
+        }
+
+        let angle = forward.angle_between(to_listener) * 2.0;
+
+        if angle <= cone.inner_angle {
+            1.0
+        } else if angle >= cone.outer_angle {
+            cone.outer_gain
+        } else {
+            let t = (angle - cone.inner_angle) / (cone.outer_angle - cone.inner_angle);
+            1.0 + (cone.outer_gain - 1.0) * t
+        }
+    }
+
+    // Gain, pan and distance as heard by `listener`.
+    fn place(&self, transform: &GlobalTransform, listener: &GlobalTransform) -> (f32, f32, f32) {
+        let offset = transform.translation() - listener.translation();
+        let distance = offset.length();
+
+        // Listener space, right is +X.
+        let local = listener.affine().inverse().transform_vector3(offset);
+        let pan = local.normalize_or_zero().x;
+
+        let to_listener = -offset.normalize_or_zero();
+        let cone = self.cone_gain(*transform.forward(), to_listener);
+        let gain = self.distance_gain(distance) * cone;
+
+        (gain, pan, distance)
+    }
+}
+
+pub(crate) fn spatialize(
+    controller: Res<AudioController>,
+    listeners: Query<&GlobalTransform, With<AudioListener>>,
+    mut emitters: Query<(
+        &AudioEmitter,
+        &GlobalTransform,
+        Ref<DawNodeId>,
+        &mut Placement,
+    )>,
+) {
+    let Some(listener) = listeners.iter().next() else {
+        return;
+    };
+
+    for (emitter, transform, id, mut placement) in &mut emitters {
+        // A new node starts from its own defaults.
+        if id.is_changed() {
+            *placement = Placement::default();
+        }
+
+        let id = id.id();
+        let (gain, pan, distance) = emitter.place(transform, listener);
+        let delay = if emitter.doppler {
+            distance / SPEED_OF_SOUND
+        } else {
+            0.0
+        };
+
+        // Smoothed by the node, so updating once per frame is enough. Failed
+        // sends are retried on the next frame.
+        for (i, value) in [gain, pan, delay].into_iter().enumerate() {
+            let last = &mut placement.0[i];
+
+            if last.is_some_and(|last| (last - value).abs() < EPSILONS[i]) {
+                continue;
+            }
+
+            if controller.set_param(id, PARAMS[i], value).is_ok() {
+                *last = Some(value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Attenuation, AudioEmitter, AudioListener, Cone, DawNodeId, spatialize};
+    use crate::engine::{AudioController, AudioLimits, DEFAULT_SAMPLE_RATE};
+    use crate::node::nodes::SpatialNode;
+    use bevy::app::{App, Update};
+    use bevy::math::Vec3;
+    use bevy::transform::components::{GlobalTransform, Transform};
+    use std::f32::consts::PI;
+
+    #[test]
+    fn emitters_are_placed_around_the_listener() {
+        let listener = GlobalTransform::from(Transform::default());
+        let at = |x, z| GlobalTransform::from(Transform::from_xyz(x, 0.0, z));
+        let emitter = AudioEmitter::default();
+
+        let (gain, pan, distance) = emitter.place(&at(4.0, 0.0), &listener);
+        assert_eq!((gain, pan, distance), (0.25, 1.0, 4.0));
+
+        let (gain, pan, _) = emitter.place(&at(-0.5, 0.0), &listener);
+        assert_eq!((gain, pan), (1.0, -1.0));
+
+        let linear = AudioEmitter {
+            attenuation: Attenuation::Linear,
+            max_distance: 11.0,
+            ..AudioEmitter::default()
+        };
+        assert_eq!(linear.distance_gain(6.0), 0.5);
+        assert_eq!(linear.distance_gain(20.0), 0.0);
+
+        let exponential = AudioEmitter {
+            attenuation: Attenuation::Exponential,
+            rolloff: 2.0,
+            ..AudioEmitter::default()
+        };
+        assert_eq!(exponential.distance_gain(2.0), 0.25);
+
+        // Facing -Z by default, the listener is behind it.
+        let cone = AudioEmitter {
+            cone: Some(Cone {
+                inner_angle: PI / 2.0,
+                outer_angle: PI,
+                outer_gain: 0.1,
+            }),
+            ..AudioEmitter::default()
+        };
+        let (gain, _, _) = cone.place(&at(0.0, -1.0), &listener);
+        assert!((gain - 0.1).abs() < 1e-6);
+        assert_eq!(cone.cone_gain(Vec3::NEG_Z, Vec3::NEG_Z), 1.0);
+    }
+
+    #[test]
+    fn only_changes_are_sent() {
+        let limits = AudioLimits {
+            command_queue: 8,
+            ..AudioLimits::default()
+        };
+        let (mut controller, _renderer) =
+            AudioController::offline_with_limits(DEFAULT_SAMPLE_RATE, 2, limits);
+        let id = controller.add_node(Box::new(SpatialNode::new())).unwrap();
+
+        let mut app = App::new();
+        app.insert_resource(controller)
+            .add_systems(Update, spatialize);
+
+        app.world_mut().spawn(AudioListener);
+        let emitter = app
+            .world_mut()
+            .spawn((AudioEmitter::default(), DawNodeId(id)))
+            .id();
+
+        // Nothing drains the queue, standing still mustn't fill it.
+        for _ in 0..10 {
+            app.update();
+        }
+
+        // Gain and pan change, the delay is off.
+        let moved = GlobalTransform::from(Transform::from_xyz(4.0, 0.0, 0.0));
+        *app.world_mut().get_mut::<GlobalTransform>(emitter).unwrap() = moved;
+        app.update();
+
+        assert_eq!(
+            app.world().resource::<AudioController>().dropped_commands(),
+            0
+        );
+    }
+}
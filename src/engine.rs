@@ -1,140 +1,1480 @@
 use super::traits::AudioNode;
-use crate::node::NodeId;
+use crate::buffer::{AudioBuffer, AudioBufferMut};
+use crate::error::DawError;
+use crate::node::nodes::GainNode;
+use crate::node::param::{ParamId, ParamInfo};
+use crate::node::{NodeEvent, NodeId, ProcessContext};
 use bevy::ecs::resource::Resource;
+use device::StreamHandle;
+use graph::{Graph, MASTER_SLOT, Schedule};
 use hashbrown::HashMap;
-use heapless::spsc::Queue;
+use load::DspLoad;
+use profile::Profiled;
+use queue::Queue;
 use spin::Mutex;
-use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 mod device;
+mod event;
+mod graph;
+mod load;
+mod offline;
+mod profile;
+mod queue;
+mod transport;
 
-pub const SAMPLE_RATE: u32 = 44_100;
-pub const MAX_BUFFER_SIZE: usize = 8192;
+pub use device::{AudioStreamEvent, DeviceSelection, OutputConfigInfo, OutputDeviceInfo};
+pub use event::{AudioEvent, AudioNodeEvent, BeatCrossed, TransportChanged};
+pub use load::DspStats;
+pub use offline::OfflineRenderer;
+pub use profile::{NodeProfile, NodeTimer};
+pub use transport::{
+    LoopRegion, MusicalTime, TICKS_PER_BEAT, TimeSignature, Transport, TransportCommand,
+};
 
-#[derive(Debug)]
-pub struct NodePtr<T: ?Sized>(*mut T);
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+pub const MAX_BUFFER_SIZE: usize = 8192;
+pub const DEFAULT_BLOCK_SIZE: usize = 256;
+pub const DEFAULT_CHANNELS: usize = 2;
 
-unsafe impl<T: ?Sized + Send> Send for NodePtr<T> {}
-unsafe impl<T: ?Sized + Sync> Sync for NodePtr<T> {}
+#[derive(Clone, Copy, Debug)]
+pub struct AudioLimits {
+    pub max_nodes: usize,
+    pub command_queue: usize,
+    pub pending_commands: usize,
+    pub events: usize,
+    // Frames the graph renders at once, whatever the device asks for.
+    pub block_size: usize,
+}
 
-impl<T: ?Sized> NodePtr<T> {
-    pub unsafe fn new(ptr: *mut T) -> Option<Self> {
-        if ptr.is_null() { None } else { Some(Self(ptr)) }
+impl Default for AudioLimits {
+    fn default() -> Self {
+        Self {
+            max_nodes: 256,
+            command_queue: 1024,
+            pending_commands: 256,
+            events: 1024,
+            block_size: DEFAULT_BLOCK_SIZE,
+        }
     }
+}
 
-    pub unsafe fn as_mut(&mut self) -> &mut T {
-        unsafe { &mut *self.0 }
+impl AudioLimits {
+    // The least the engine can run with, the master takes a node slot.
+    pub(crate) fn clamped(self) -> Self {
+        Self {
+            max_nodes: self.max_nodes.max(MASTER_SLOT + 1),
+            command_queue: self.command_queue.max(1),
+            pending_commands: self.pending_commands,
+            events: self.events.max(1),
+            block_size: self.block_size.clamp(1, MAX_BUFFER_SIZE),
+        }
     }
+}
 
-    pub unsafe fn into_box(self) -> Box<T> {
-        unsafe { Box::from_raw(self.0) }
-    }
+#[derive(Debug)]
+struct NodeSlot {
+    id: NodeId,
+    node: Box<dyn AudioNode>,
+    buffer: AudioBuffer,
+    // Frames left to play once the node reported it's finished.
+    tail: Option<u64>,
+    // Sample rate and channel count the node and buffer were prepared for.
+    format: (u32, usize),
 }
 
-impl<T: ?Sized> Clone for NodePtr<T> {
-    fn clone(&self) -> Self {
-        Self(self.0)
-    }
+#[derive(Debug)]
+enum AudioCommand {
+    AddNode(usize, NodeSlot),
+    RemoveNode(usize, NodeId),
+    SetSchedule(Box<Schedule>),
+    // Tagged with the node, slots get reused.
+    SetParam(usize, NodeId, ParamId, f32),
+    Transport(TransportCommand),
 }
 
+// Applied on the first frame at or after `at`, anything in the past lands immediately.
 #[derive(Debug)]
-pub(super) enum AudioCommand {
-    AddNode(NodePtr<dyn AudioNode>),
-    RemoveNode(NodePtr<dyn AudioNode>),
+struct TimedCommand {
+    at: u64,
+    cmd: AudioCommand,
 }
 
-thread_local! {
-    static AUDIO_STATE: RefCell<AudioEngine> = RefCell::new(AudioEngine::empty());
+impl AudioCommand {
+    fn at(self, at: u64) -> TimedCommand {
+        TimedCommand { at, cmd: self }
+    }
+
+    fn now(self) -> TimedCommand {
+        self.at(0)
+    }
 }
 
-static AUDIO_QUEUE: Mutex<Queue<AudioCommand, 64>> = Mutex::new(Queue::new());
+// Released by the audio thread, dropped on the main thread by `collect_garbage`.
+#[derive(Debug)]
+enum Garbage {
+    Node(usize, NodeSlot),
+    // Removed by the engine itself, the controller still has it in the graph.
+    Finished(usize, NodeSlot),
+    // Sent while the stream changed format, has to be prepared again.
+    Unprepared(usize, NodeSlot),
+    Schedule(Box<Schedule>),
+}
+
+// Everything the controller and the audio thread both touch.
+#[derive(Debug)]
+struct Shared {
+    commands: Queue<TimedCommand>,
+    garbage: Queue<Garbage>,
+    events: Queue<AudioEvent>,
+    clock: AtomicU64,
+    transport: Mutex<Transport>,
+    dropped_commands: AtomicU64,
+    early_commands: AtomicU64,
+    load: DspLoad,
+    profiling: AtomicBool,
+    // What the engine was last prepared for, the controller only catches up
+    // when it polls the stream.
+    format: Mutex<(u32, usize)>,
+    // One per node slot, cleared when the slot gets a new node.
+    timers: Box<[NodeTimer]>,
+}
 
-#[derive(Default, Debug)]
+#[derive(Debug)]
 pub struct AudioEngine {
-    nodes: heapless::Vec<NodePtr<dyn AudioNode>, 256>,
-    sample_pos: u32,
+    slots: Vec<Option<NodeSlot>>,
+    schedule: Box<Schedule>,
+    pending: Vec<TimedCommand>,
+    garbage: Vec<Garbage>,
+    shared: Arc<Shared>,
+    limits: AudioLimits,
+    sample_pos: u64,
+    transport: Transport,
+    sample_rate: u32,
+    block_size: usize,
+    channels: usize,
+    // Rendered but not yet handed to the device, read from `block_pos` on.
+    block: AudioBuffer,
+    block_pos: usize,
 }
 
 impl AudioEngine {
-    pub fn empty() -> Self {
+    pub fn new(limits: AudioLimits) -> Self {
+        let limits = limits.clamped();
+        let block_size = limits.block_size;
+        let mut slots: Vec<Option<NodeSlot>> = (0..limits.max_nodes).map(|_| None).collect();
+        // Every command can release at most one node or schedule.
+        let garbage = limits.max_nodes + limits.command_queue;
+
+        slots[MASTER_SLOT] = Some(NodeSlot {
+            id: NodeId::MASTER,
+            tail: None,
+            node: Box::new(GainNode::default()),
+            buffer: AudioBuffer::new(DEFAULT_CHANNELS, block_size),
+            format: (DEFAULT_SAMPLE_RATE, DEFAULT_CHANNELS),
+        });
+
         Self {
+            slots,
+            schedule: Box::new(Graph::new().compile()),
+            pending: Vec::with_capacity(limits.pending_commands),
+            garbage: Vec::with_capacity(garbage),
+            shared: Arc::new(Shared {
+                commands: Queue::new(limits.command_queue),
+                garbage: Queue::new(garbage),
+                events: Queue::new(limits.events),
+                clock: AtomicU64::new(0),
+                transport: Mutex::new(Transport::default()),
+                dropped_commands: AtomicU64::new(0),
+                early_commands: AtomicU64::new(0),
+                load: DspLoad::default(),
+                profiling: AtomicBool::new(cfg!(debug_assertions)),
+                format: Mutex::new((DEFAULT_SAMPLE_RATE, DEFAULT_CHANNELS)),
+                timers: (0..limits.max_nodes)
+                    .map(|_| NodeTimer::default())
+                    .collect(),
+            }),
+            limits,
             sample_pos: 0,
-            nodes: heapless::Vec::new(),
+            transport: Transport::default(),
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            block_size,
+            channels: DEFAULT_CHANNELS,
+            block: AudioBuffer::new(DEFAULT_CHANNELS, block_size),
+            block_pos: block_size,
+        }
+    }
+
+    // Main thread only, nodes are free to allocate here.
+    pub fn prepare(&mut self, sample_rate: u32, channels: usize) {
+        self.sample_rate = sample_rate;
+        self.channels = channels;
+        self.block = AudioBuffer::new(channels, self.block_size);
+        self.block_pos = self.block_size;
+        self.transport.set_sample_rate(sample_rate);
+        *self.shared.transport.lock() = self.transport;
+        *self.shared.format.lock() = (sample_rate, channels);
+
+        let waiting = self
+            .pending
+            .iter_mut()
+            .filter_map(|timed| match &mut timed.cmd {
+                AudioCommand::AddNode(_, slot) => Some(slot),
+                _ => None,
+            });
+
+        for slot in self.slots.iter_mut().flatten().chain(waiting) {
+            slot.node.prepare(sample_rate, self.block_size, channels);
+            slot.buffer = AudioBuffer::new(channels, self.block_size);
+            slot.format = (sample_rate, channels);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.sample_pos = 0;
+        self.block_pos = self.block_size;
+        self.shared.clock.store(0, Ordering::Relaxed);
+        self.transport.apply(TransportCommand::Seek(0));
+        *self.shared.transport.lock() = self.transport;
+
+        for slot in self.slots.iter_mut().flatten() {
+            slot.node.reset();
         }
     }
 
     fn on_command(&mut self, cmd: AudioCommand) {
         match cmd {
-            AudioCommand::AddNode(ptr) => {
-                self.nodes.push(ptr).ok();
+            AudioCommand::AddNode(index, slot) => {
+                if slot.format != (self.sample_rate, self.channels) {
+                    self.release(Garbage::Unprepared(index, slot));
+                    return;
+                }
+
+                if let Some(old) = self.slots[index].replace(slot) {
+                    self.purge(index, old.id);
+                    self.release(Garbage::Node(index, old));
+                }
+            }
+            AudioCommand::RemoveNode(index, id) => {
+                // The node may have finished and its slot been reused since.
+                if let Some(slot) = self.slots[index].take_if(|slot| slot.id == id) {
+                    self.purge(index, slot.id);
+                    self.release(Garbage::Node(index, slot));
+                } else if let Some(slot) = self.cancel_add(index, id) {
+                    // Removed before its scheduled start, it never gets to play.
+                    self.purge(index, slot.id);
+                    self.release(Garbage::Node(index, slot));
+                }
             }
-            AudioCommand::RemoveNode(ptr) => {
-                self.nodes.retain(|node| !std::ptr::addr_eq(node.0, ptr.0));
+            AudioCommand::SetSchedule(schedule) => {
+                let old = std::mem::replace(&mut self.schedule, schedule);
+                self.release(Garbage::Schedule(old));
+            }
+            AudioCommand::SetParam(index, id, param, value) => {
+                if let Some(slot) = &mut self.slots[index]
+                    && slot.id == id
+                {
+                    slot.node.set_param(param, value);
+                }
+            }
+            AudioCommand::Transport(cmd) => {
+                self.transport.apply(cmd);
+                self.emit(AudioEvent::Transport(TransportChanged(self.transport)));
             }
         };
     }
 
-    fn process(&mut self, buf: &mut [f32]) {
-        buf.fill(0.0);
+    fn cancel_add(&mut self, index: usize, id: NodeId) -> Option<NodeSlot> {
+        let i = self.pending.iter().position(|timed| {
+            matches!(&timed.cmd, AudioCommand::AddNode(i, slot) if (*i, slot.id) == (index, id))
+        })?;
+
+        match self.pending.remove(i).cmd {
+            AudioCommand::AddNode(_, slot) => Some(slot),
+            _ => None,
+        }
+    }
+
+    // Whatever is still scheduled for a node that's gone would otherwise land
+    // on the next node in its slot.
+    fn purge(&mut self, index: usize, id: NodeId) {
+        self.pending.retain(|timed| match timed.cmd {
+            AudioCommand::RemoveNode(i, target) | AudioCommand::SetParam(i, target, ..) => {
+                (i, target) != (index, id)
+            }
+            _ => true,
+        });
+    }
+
+    fn schedule_command(&mut self, timed: TimedCommand) {
+        if timed.at <= self.sample_pos {
+            self.on_command(timed.cmd);
+        } else if self.pending.len() == self.limits.pending_commands {
+            // Early is better than lost, the controller has no way to retry.
+            self.shared.early_commands.fetch_add(1, Ordering::Relaxed);
+            self.on_command(timed.cmd);
+        } else {
+            self.pending.push(timed);
+        }
+    }
+
+    // Queue order is kept between commands due on the same frame.
+    fn apply_due(&mut self) {
+        while let Some(i) = self
+            .pending
+            .iter()
+            .position(|timed| timed.at <= self.sample_pos)
+        {
+            let timed = self.pending.remove(i);
+            self.on_command(timed.cmd);
+        }
+    }
+
+    fn next_due(&self) -> Option<u64> {
+        self.pending.iter().map(|timed| timed.at).min()
+    }
+
+    fn finish(&mut self, index: usize, slot: NodeSlot) {
+        self.purge(index, slot.id);
+
+        let node = slot.id;
+        self.emit(AudioEvent::Node(AudioNodeEvent {
+            node,
+            event: NodeEvent::Finished,
+        }));
+        self.release(Garbage::Finished(index, slot));
+    }
+
+    fn emit(&self, event: AudioEvent) {
+        // Nobody is reading them if the queue is full.
+        self.shared.events.push(event).ok();
+    }
+
+    fn release(&mut self, garbage: Garbage) {
+        if self.garbage.len() == self.garbage.capacity() {
+            // Never free on the audio thread, leaking is the lesser evil.
+            std::mem::forget(garbage);
+        } else {
+            self.garbage.push(garbage);
+        }
+    }
+
+    fn flush_garbage(&mut self) {
+        while let Some(garbage) = self.garbage.pop() {
+            if let Err(garbage) = self.shared.garbage.push(garbage) {
+                self.garbage.push(garbage);
+                break;
+            }
+        }
+    }
+
+    // Stands in for the audio thread when nothing renders, every command lands
+    // right away and nodes go straight back to the controller.
+    fn discard_commands(&mut self) {
+        while let Some(timed) = self.shared.commands.pop() {
+            self.on_command(timed.cmd);
+        }
+
+        self.flush_garbage();
+    }
+
+    fn receive_commands(&mut self) {
+        while let Some(timed) = self.shared.commands.pop() {
+            self.schedule_command(timed);
+        }
+    }
+
+    // Device callbacks come in any size, the graph always renders whole
+    // blocks and the leftover frames are handed out by the next callback.
+    fn pull<S>(&mut self, data: &mut [S], convert: impl Fn(f32) -> S) {
+        let channels = self.channels;
+
+        for frame in data.chunks_exact_mut(channels) {
+            if self.block_pos == self.block_size {
+                let mut block = std::mem::take(&mut self.block);
+                self.process(&mut block.as_mut());
+                self.block = block;
+                self.block_pos = 0;
+            }
+
+            for (ch, sample) in frame.iter_mut().enumerate() {
+                *sample = convert(self.block.channel(ch)[self.block_pos]);
+            }
+
+            self.block_pos += 1;
+        }
+    }
+
+    fn process(&mut self, output: &mut AudioBufferMut) {
+        output.fill(0.0);
+        self.receive_commands();
+
+        // Split the block on pending commands so each one lands on its exact frame.
+        let frames = output.frames();
+        let mut start = 0;
+
+        while start < frames {
+            self.apply_due();
+
+            let remaining = (frames - start) as u64;
+            let len = [
+                self.next_due().map(|at| at - self.sample_pos),
+                self.transport.frames_until_wrap(),
+            ]
+            .into_iter()
+            .flatten()
+            .fold(remaining, u64::min);
+            let end = start + len as usize;
+
+            self.render(&mut output.slice_mut(start..end));
+
+            for beat in self.transport.beats_within(len) {
+                let time = self.transport.beat_to_musical(beat);
+                self.emit(AudioEvent::Beat(BeatCrossed(time)));
+            }
+
+            self.sample_pos += len;
+            self.transport.advance(len);
+            start = end;
+        }
 
-        let mut queue = AUDIO_QUEUE.lock();
-        let (_, mut consumer) = queue.split();
+        self.shared.clock.store(self.sample_pos, Ordering::Relaxed);
 
-        while let Some(cmd) = consumer.dequeue() {
-            self.on_command(cmd);
+        // Skipped if the main thread is reading it, the next block catches up.
+        if let Some(mut transport) = self.shared.transport.try_lock() {
+            *transport = self.transport;
         }
+        self.flush_garbage();
+    }
+
+    fn render(&mut self, output: &mut AudioBufferMut) {
+        let frames = output.frames();
+        let ctx = ProcessContext {
+            sample_pos: self.sample_pos,
+            transport: self.transport,
+            profiling: self.shared.profiling.load(Ordering::Relaxed),
+        };
 
-        for node_ptr in &mut self.nodes {
-            unsafe {
-                node_ptr.as_mut().process(self.sample_pos, buf);
+        for step in 0..self.schedule.steps.len() {
+            let index = self.schedule.steps[step].slot;
+            let Some(mut slot) = self.slots[index].take() else {
+                continue;
+            };
+
+            // Removed a block late, so whatever it feeds still got its last output.
+            if slot.tail == Some(0) {
+                self.finish(index, slot);
+                continue;
             }
+
+            let mut buffer = slot.buffer.slice_mut(0..frames);
+            buffer.fill(0.0);
+
+            for input in &self.schedule.steps[step].inputs {
+                if let Some(input) = &self.slots[*input] {
+                    buffer.add_from(&input.buffer.slice(0..frames));
+                }
+            }
+
+            self.shared.timers[index]
+                .measure(ctx.profiling, || slot.node.process(&ctx, &mut buffer));
+
+            while let Some(event) = slot.node.take_event() {
+                let node = slot.id;
+                self.emit(AudioEvent::Node(AudioNodeEvent { node, event }));
+            }
+
+            slot.tail = match slot.tail {
+                Some(tail) => Some(tail.saturating_sub(frames as u64)),
+                None if slot.node.is_finished() => {
+                    let tail = slot.node.tail().as_secs_f64() * self.sample_rate as f64;
+                    Some(tail as u64)
+                }
+                None => None,
+            };
+
+            self.slots[index] = Some(slot);
         }
 
-        self.sample_pos = self.sample_pos.wrapping_add(buf.len() as u32);
+        if let Some(master) = &self.slots[MASTER_SLOT] {
+            output.add_from(&master.buffer.slice(0..frames));
+        }
+    }
+}
+
+#[derive(Debug, Resource)]
+pub enum AudioStatus {
+    Live,
+    Degraded(DawError),
+}
+
+impl AudioStatus {
+    pub fn is_live(&self) -> bool {
+        matches!(self, Self::Live)
     }
 }
 
 #[derive(Debug, Resource)]
 pub struct AudioController {
-    nodes: HashMap<NodeId, NodePtr<dyn AudioNode>>,
+    graph: Graph,
+    params: HashMap<NodeId, Vec<ParamInfo>>,
+    profiled: HashMap<NodeId, Profiled>,
+    retiring: HashMap<usize, NodeId>,
+    retired: Vec<NodeId>,
+    unprepared: Vec<(usize, NodeSlot)>,
+    shared: Arc<Shared>,
+    // Drained by the controller itself when there is no audio thread.
+    idle: Option<Mutex<AudioEngine>>,
+    stream: Option<StreamHandle>,
+    device: Option<String>,
+    inputs: HashMap<NodeId, u64>,
+    free_slots: Vec<usize>,
+    max_nodes: usize,
+    next_slot: usize,
     next_id: u32,
+    sample_rate: u32,
+    block_size: usize,
+    channels: usize,
 }
 
 impl AudioController {
-    pub fn add_node(&mut self, node: Box<dyn AudioNode>) -> Option<NodeId> {
-        unsafe {
-            let static_node: &'static mut dyn AudioNode = Box::leak(node);
+    fn for_engine(engine: &AudioEngine) -> Self {
+        Self {
+            graph: Graph::new(),
+            params: HashMap::from([(NodeId::MASTER, GainNode::default().params().to_vec())]),
+            profiled: HashMap::from([(NodeId::MASTER, Profiled::new(&GainNode::default()))]),
+            retiring: HashMap::new(),
+            retired: Vec::new(),
+            unprepared: Vec::new(),
+            shared: engine.shared.clone(),
+            idle: None,
+            stream: None,
+            device: None,
+            inputs: HashMap::new(),
+            free_slots: Vec::new(),
+            max_nodes: engine.limits.max_nodes,
+            next_slot: MASTER_SLOT + 1,
+            next_id: 0,
+            sample_rate: engine.sample_rate,
+            block_size: engine.block_size,
+            channels: engine.channels,
+        }
+    }
+
+    pub fn offline(sample_rate: u32, channels: usize) -> (Self, OfflineRenderer) {
+        Self::offline_with_limits(sample_rate, channels, AudioLimits::default())
+    }
+
+    pub fn offline_with_limits(
+        sample_rate: u32,
+        channels: usize,
+        limits: AudioLimits,
+    ) -> (Self, OfflineRenderer) {
+        let renderer = OfflineRenderer::new(sample_rate, channels, limits);
+        (Self::for_engine(&renderer.engine), renderer)
+    }
+
+    // Keeps working without rendering anything, for headless apps.
+    pub fn null(sample_rate: u32, channels: usize) -> Self {
+        Self::null_with_limits(sample_rate, channels, AudioLimits::default())
+    }
+
+    pub fn null_with_limits(sample_rate: u32, channels: usize, limits: AudioLimits) -> Self {
+        let mut engine = AudioEngine::new(limits);
+        engine.prepare(sample_rate, channels);
+
+        let mut controller = Self::for_engine(&engine);
+        controller.idle = Some(Mutex::new(engine));
+        controller
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn master(&self) -> NodeId {
+        NodeId::MASTER
+    }
+
+    // Frames rendered so far, the reference for every `*_at` call.
+    pub fn sample_pos(&self) -> u64 {
+        self.shared.clock.load(Ordering::Relaxed)
+    }
+
+    // Commands refused because the queue was full, since startup.
+    pub fn dropped_commands(&self) -> u64 {
+        self.shared.dropped_commands.load(Ordering::Relaxed)
+    }
+
+    // Timed commands applied ahead of time because too many were pending,
+    // since startup.
+    pub fn early_commands(&self) -> u64 {
+        self.shared.early_commands.load(Ordering::Relaxed)
+    }
+
+    // On by default in debug builds.
+    pub fn set_profiling(&self, enabled: bool) {
+        self.shared.profiling.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_profiling(&self) -> bool {
+        self.shared.profiling.load(Ordering::Relaxed)
+    }
+
+    // Time spent per node since it was added, most expensive first.
+    pub fn profile(&self) -> Vec<NodeProfile> {
+        let mut report = Vec::new();
+
+        for (id, profiled) in &self.profiled {
+            if let Some(slot) = self.graph.slot(*id) {
+                profiled.report(*id, &self.shared.timers[slot], &mut report);
+            }
+        }
+
+        report.sort_by_key(|profile| Reverse(profile.total));
+        report
+    }
+
+    // Everything the audio thread reported since the last call.
+    pub fn audio_events(&self) -> Vec<AudioEvent> {
+        std::iter::from_fn(|| self.shared.events.pop()).collect()
+    }
+
+    // Load since the last call, only live streams report any.
+    pub fn dsp_stats(&self) -> DspStats {
+        self.shared.load.take()
+    }
+
+    // As of the last rendered block.
+    pub fn transport(&self) -> Transport {
+        *self.shared.transport.lock()
+    }
+
+    pub fn send_transport(&self, cmd: TransportCommand) -> Result<(), DawError> {
+        self.send_transport_at(cmd, 0)
+    }
+
+    pub fn send_transport_at(&self, cmd: TransportCommand, at: u64) -> Result<(), DawError> {
+        self.send_commands([AudioCommand::Transport(cmd).at(at)])
+    }
+
+    pub fn add_node(&mut self, node: Box<dyn AudioNode>) -> Result<NodeId, DawError> {
+        self.add_node_at(node, 0)
+    }
+
+    // The node is routed right away but stays silent until `at`.
+    pub fn add_node_at(&mut self, node: Box<dyn AudioNode>, at: u64) -> Result<NodeId, DawError> {
+        let id = NodeId(self.next_id + 1);
+        let index = match self.free_slots.pop() {
+            Some(index) => index,
+            None if self.next_slot < self.max_nodes => self.next_slot,
+            None => return Err(DawError::NodeTableFull(self.max_nodes)),
+        };
+
+        let mut slot = NodeSlot {
+            id,
+            tail: None,
+            node,
+            buffer: AudioBuffer::new(0, 0),
+            format: (0, 0),
+        };
+        self.prepare_slot(&mut slot);
+
+        let params = slot.node.params().to_vec();
+        let profiled = Profiled::new(&*slot.node);
+
+        self.graph.insert(id, index);
+        let schedule = Box::new(self.graph.compile());
+
+        if let Err(err) = self.send_commands([
+            AudioCommand::AddNode(index, slot).at(at),
+            AudioCommand::SetSchedule(schedule).now(),
+        ]) {
+            self.graph.remove(id);
+            self.free_slots.push(index);
+            return Err(err);
+        }
+
+        if index == self.next_slot {
+            self.next_slot += 1;
+        }
+
+        self.params.insert(id, params);
+        self.profiled.insert(id, profiled);
+        self.shared.timers[index].clear();
+
+        self.next_id += 1;
+
+        Ok(id)
+    }
+
+    // The engine's format rather than `sample_rate`, which lags behind after
+    // the stream was rebuilt.
+    fn prepare_slot(&self, slot: &mut NodeSlot) {
+        let (sample_rate, channels) = *self.shared.format.lock();
+
+        slot.node.prepare(sample_rate, self.block_size, channels);
+        slot.buffer = AudioBuffer::new(channels, self.block_size);
+        slot.format = (sample_rate, channels);
+    }
+
+    pub fn remove_node(&mut self, id: NodeId) -> Result<(), DawError> {
+        self.check_removable(id)?;
+
+        let snapshot = self.graph.clone();
+        let index = self.graph.remove(id).ok_or(DawError::UnknownNode(id))?;
+        let schedule = Box::new(self.graph.compile());
+
+        if let Err(err) = self.send_commands([
+            AudioCommand::SetSchedule(schedule).now(),
+            AudioCommand::RemoveNode(index, id).now(),
+        ]) {
+            self.graph = snapshot;
+            return Err(err);
+        }
+
+        self.free_slots.push(index);
+        self.params.remove(&id);
+        self.profiled.remove(&id);
+        self.release_input(id);
+
+        Ok(())
+    }
+
+    // Stays in the graph until the audio thread hands the node back, so its
+    // slot can't be reused while the removal is still pending.
+    pub fn remove_node_at(&mut self, id: NodeId, at: u64) -> Result<(), DawError> {
+        self.check_removable(id)?;
+
+        let index = self.graph.slot(id).ok_or(DawError::UnknownNode(id))?;
+        self.send_commands([AudioCommand::RemoveNode(index, id).at(at)])?;
+        self.retiring.insert(index, id);
+
+        Ok(())
+    }
+
+    fn check_removable(&self, id: NodeId) -> Result<(), DawError> {
+        if id == NodeId::MASTER {
+            return Err(DawError::MasterNode);
+        }
+
+        if self.retiring.values().any(|retiring| *retiring == id) {
+            return Err(DawError::UnknownNode(id));
+        }
+
+        Ok(())
+    }
+
+    pub fn params(&self, id: NodeId) -> Option<&[ParamInfo]> {
+        self.params.get(&id).map(Vec::as_slice)
+    }
+
+    // Values are clamped to the parameter range and smoothed by the node.
+    pub fn set_param(&self, id: NodeId, param: ParamId, value: f32) -> Result<(), DawError> {
+        self.set_param_at(id, param, value, 0)
+    }
+
+    pub fn set_param_at(
+        &self,
+        id: NodeId,
+        param: ParamId,
+        value: f32,
+        at: u64,
+    ) -> Result<(), DawError> {
+        let info = self
+            .params
+            .get(&id)
+            .ok_or(DawError::UnknownNode(id))?
+            .iter()
+            .find(|info| info.id == param)
+            .ok_or(DawError::UnknownParam(id, param))?;
+        let index = self.graph.slot(id).ok_or(DawError::UnknownNode(id))?;
+
+        self.send_commands([AudioCommand::SetParam(index, id, param, info.clamp(value)).at(at)])
+    }
+
+    pub fn connect(&mut self, from: NodeId, to: NodeId) -> Result<(), DawError> {
+        self.graph.connect(from, to)?;
+
+        if let Err(err) = self.update_schedule() {
+            self.graph.disconnect(from, to).ok();
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    pub fn disconnect(&mut self, from: NodeId, to: NodeId) -> Result<(), DawError> {
+        self.graph.disconnect(from, to)?;
+
+        if let Err(err) = self.update_schedule() {
+            self.graph.connect(from, to).ok();
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    fn update_schedule(&self) -> Result<(), DawError> {
+        let schedule = Box::new(self.graph.compile());
+        self.send_commands([AudioCommand::SetSchedule(schedule).now()])
+    }
+
+    pub fn collect_garbage(&mut self) -> usize {
+        let mut nodes = 0;
+
+        while let Some(garbage) = self.shared.garbage.pop() {
+            match garbage {
+                Garbage::Node(index, slot) => {
+                    // The index may belong to a newer node by now.
+                    if self.retiring.get(&index) == Some(&slot.id) {
+                        self.retiring.remove(&index);
+                        self.retired.push(slot.id);
+                    }
+
+                    drop(slot);
+                    nodes += 1;
+                }
+                Garbage::Finished(index, slot) => {
+                    if self.retiring.get(&index) == Some(&slot.id) {
+                        self.retiring.remove(&index);
+                    }
+
+                    // Already gone if it was removed while finishing.
+                    if self.graph.slot(slot.id) == Some(index) {
+                        self.retired.push(slot.id);
+                    }
+
+                    drop(slot);
+                    nodes += 1;
+                }
+                // Retired early rather than coming back after its removal was due.
+                Garbage::Unprepared(index, slot) if self.retiring.get(&index) == Some(&slot.id) => {
+                    self.retiring.remove(&index);
+                    self.retired.push(slot.id);
+                    drop(slot);
+                    nodes += 1;
+                }
+                Garbage::Unprepared(index, slot) => self.unprepared.push((index, slot)),
+                Garbage::Schedule(schedule) => drop(schedule),
+            }
+        }
+
+        // Removed while they were away, nothing else is going to release them.
+        let waiting = self.unprepared.len();
+        self.unprepared
+            .retain(|(index, slot)| self.graph.slot(slot.id) == Some(*index));
+        nodes += waiting - self.unprepared.len();
+
+        self.retire_nodes();
+        self.readd_nodes();
+
+        nodes
+    }
+
+    // Retried on the next collection if the queue is full.
+    fn readd_nodes(&mut self) {
+        while let Some((index, mut slot)) = self.unprepared.pop() {
+            self.prepare_slot(&mut slot);
+
+            let timed = AudioCommand::AddNode(index, slot).now();
+
+            if let Err(TimedCommand {
+                cmd: AudioCommand::AddNode(index, slot),
+                ..
+            }) = self.shared.commands.push(timed)
+            {
+                self.unprepared.push((index, slot));
+                return;
+            }
+        }
+    }
+
+    // Retried on the next collection if the queue is full.
+    fn retire_nodes(&mut self) {
+        if self.retired.is_empty() {
+            return;
+        }
+
+        let snapshot = self.graph.clone();
+
+        for id in &self.retired {
+            self.graph.remove(*id);
+        }
+
+        if self.update_schedule().is_err() {
+            self.graph = snapshot;
+            return;
+        }
+
+        while let Some(id) = self.retired.pop() {
+            self.free_slots.extend(snapshot.slot(id));
+            self.params.remove(&id);
+            self.profiled.remove(&id);
+            self.release_input(id);
+        }
+    }
+
+    fn send_commands<const N: usize>(&self, cmds: [TimedCommand; N]) -> Result<(), DawError> {
+        if self.shared.commands.push_all(cmds).is_err() {
+            self.shared
+                .dropped_commands
+                .fetch_add(N as u64, Ordering::Relaxed);
+            return Err(DawError::CommandQueueFull);
+        }
+
+        if let Some(engine) = &self.idle {
+            engine.lock().discard_commands();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        AudioController, AudioEvent, AudioLimits, AudioNodeEvent, BeatCrossed, DEFAULT_BLOCK_SIZE,
+        DEFAULT_CHANNELS, DEFAULT_SAMPLE_RATE, MAX_BUFFER_SIZE, MusicalTime, TransportChanged,
+        TransportCommand,
+    };
+    use crate::buffer::AudioBufferMut;
+    use crate::error::DawError;
+    use crate::node::nodes::{EnvelopeNode, GainNode, GroupNode, MeterNode, ToneGeneratorNode};
+    use crate::node::param::ParamId;
+    use crate::node::{NodeEvent, NodeId, ProcessContext};
+    use crate::traits::AudioNode;
+    use std::time::Duration;
+
+    #[test]
+    fn removed_nodes_are_returned_to_main_thread() {
+        let (mut controller, mut renderer) =
+            AudioController::offline(DEFAULT_SAMPLE_RATE, DEFAULT_CHANNELS);
+
+        let id = controller
+            .add_node(Box::new(ToneGeneratorNode::new(440.0_f32, 1.0)))
+            .unwrap();
+        controller.connect(id, controller.master()).unwrap();
+        renderer.render_frames(64);
+
+        controller.remove_node(id).unwrap();
+        assert_eq!(controller.collect_garbage(), 0);
+
+        renderer.render_frames(64);
+        assert_eq!(controller.collect_garbage(), 1);
+
+        assert!(renderer.render_frames(64).iter().all(|s| *s == 0.0));
+        assert!(matches!(
+            controller.remove_node(id),
+            Err(DawError::UnknownNode(stale)) if stale == id
+        ));
+    }
+
+    #[test]
+    fn only_nodes_routed_to_master_are_heard() {
+        let (mut controller, mut renderer) =
+            AudioController::offline(DEFAULT_SAMPLE_RATE, DEFAULT_CHANNELS);
+
+        let tone = controller
+            .add_node(Box::new(ToneGeneratorNode::new(440.0_f32, 1.0)))
+            .unwrap();
+        assert!(renderer.render_frames(64).iter().all(|s| *s == 0.0));
+
+        controller.connect(tone, controller.master()).unwrap();
+        assert!(renderer.render_frames(64).iter().any(|s| *s != 0.0));
+
+        controller.disconnect(tone, controller.master()).unwrap();
+        assert!(renderer.render_frames(64).iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn params_are_validated_and_smoothed() {
+        let (mut controller, mut renderer) = AudioController::offline(DEFAULT_SAMPLE_RATE, 1);
+
+        let gain = controller.add_node(Box::new(GainNode::new(1.0))).unwrap();
+        let tone = controller
+            .add_node(Box::new(ToneGeneratorNode::new(0.0_f32, 1.0)))
+            .unwrap();
+        controller.connect(tone, gain).unwrap();
+        controller.connect(gain, controller.master()).unwrap();
+
+        // Glide the tone up from silence, then pull the gain down.
+        controller
+            .set_param(tone, ToneGeneratorNode::FREQUENCY, 1000.0)
+            .unwrap();
+        renderer.render_frames(DEFAULT_SAMPLE_RATE as usize / 10);
+
+        controller.set_param(gain, GainNode::GAIN, 0.25).unwrap();
+        let ramp = renderer.render_frames(DEFAULT_SAMPLE_RATE as usize / 10);
+
+        let peak = |samples: &[f32]| samples.iter().fold(0.0f32, |a, s| a.max(s.abs()));
+        assert!(peak(&ramp[..100]) > 0.5);
+        assert!(peak(&ramp[ramp.len() - 1000..]) < 0.26);
+
+        assert!(matches!(
+            controller.set_param(gain, ParamId(42), 1.0),
+            Err(DawError::UnknownParam(..))
+        ));
+    }
+
+    #[test]
+    fn scheduled_commands_land_on_exact_frames() {
+        let (mut controller, mut renderer) = AudioController::offline(DEFAULT_SAMPLE_RATE, 1);
+        let start = controller.sample_pos();
+
+        // Off the block boundaries on purpose.
+        let tone = controller
+            .add_node_at(
+                Box::new(ToneGeneratorNode::new(1000.0_f32, 1.0)),
+                start + 100,
+            )
+            .unwrap();
+        controller.connect(tone, controller.master()).unwrap();
+        controller.remove_node_at(tone, start + 300).unwrap();
+
+        let output = renderer.render_frames(400);
+
+        assert!(output[..101].iter().all(|s| *s == 0.0));
+        assert!(output[101..300].iter().all(|s| *s != 0.0));
+        assert!(output[300..].iter().all(|s| *s == 0.0));
+
+        assert!(matches!(
+            controller.remove_node(tone),
+            Err(DawError::UnknownNode(_))
+        ));
+        assert_eq!(controller.collect_garbage(), 1);
+        assert!(controller.params(tone).is_none());
+    }
+
+    #[test]
+    fn nodes_removed_before_their_start_never_play() {
+        let (mut controller, mut renderer) = AudioController::offline(DEFAULT_SAMPLE_RATE, 1);
+        let start = controller.sample_pos();
+
+        let tone = controller
+            .add_node_at(
+                Box::new(ToneGeneratorNode::new(1000.0_f32, 1.0)),
+                start + 100,
+            )
+            .unwrap();
+        controller.connect(tone, controller.master()).unwrap();
+        controller.remove_node(tone).unwrap();
+
+        assert!(renderer.render_frames(400).iter().all(|s| *s == 0.0));
+        assert_eq!(controller.collect_garbage(), 1);
+    }
+
+    #[test]
+    fn scheduled_params_die_with_their_node() {
+        let (mut controller, mut renderer) = AudioController::offline(DEFAULT_SAMPLE_RATE, 1);
+        let start = controller.sample_pos();
+
+        let tone = controller
+            .add_node(Box::new(ToneGeneratorNode::new(1000.0_f32, 1.0)))
+            .unwrap();
+        controller
+            .set_param_at(tone, ToneGeneratorNode::FREQUENCY, 10_000.0, start + 1000)
+            .unwrap();
+        controller.remove_node(tone).unwrap();
+        renderer.render_frames(64);
+        assert_eq!(controller.collect_garbage(), 1);
+
+        // Takes over the tone's slot, its frequency is way out of the gain's range.
+        let gain = controller.add_node(Box::new(GainNode::new(1.0))).unwrap();
+        let source = controller
+            .add_node(Box::new(ToneGeneratorNode::new(1000.0_f32, 1.0)))
+            .unwrap();
+        controller.connect(source, gain).unwrap();
+        controller.connect(gain, controller.master()).unwrap();
+
+        let output = renderer.render_frames(4096);
+        assert!(output.iter().all(|s| s.abs() <= 1.0));
+    }
+
+    #[test]
+    fn late_garbage_leaves_the_slot_next_node_alone() {
+        let (mut controller, mut renderer) = AudioController::offline(DEFAULT_SAMPLE_RATE, 1);
+
+        let a = controller.add_node(Box::new(GainNode::new(1.0))).unwrap();
+        controller.remove_node(a).unwrap();
+
+        // Takes over a's slot before a made it back to the main thread.
+        let b = controller
+            .add_node(Box::new(ToneGeneratorNode::new(1000.0_f32, 1.0)))
+            .unwrap();
+        controller.connect(b, controller.master()).unwrap();
+        let later = controller.sample_pos() + DEFAULT_SAMPLE_RATE as u64;
+        controller.remove_node_at(b, later).unwrap();
+
+        renderer.render_frames(512);
+        assert_eq!(controller.collect_garbage(), 1);
+
+        assert!(controller.params(b).is_some());
+        assert!(renderer.render_frames(512).iter().any(|s| *s != 0.0));
+    }
+
+    #[test]
+    fn overflow_is_reported_to_the_caller() {
+        let limits = AudioLimits {
+            max_nodes: 3,
+            command_queue: 4,
+            pending_commands: 4,
+            ..AudioLimits::default()
+        };
+        let (mut controller, mut renderer) =
+            AudioController::offline_with_limits(DEFAULT_SAMPLE_RATE, 1, limits);
+
+        let a = controller.add_node(Box::new(GainNode::new(1.0))).unwrap();
+        controller.add_node(Box::new(GainNode::new(1.0))).unwrap();
+        renderer.render_frames(64);
+
+        // The master takes one slot.
+        assert!(matches!(
+            controller.add_node(Box::new(GainNode::new(1.0))),
+            Err(DawError::NodeTableFull(3))
+        ));
+
+        for _ in 0..4 {
+            controller.set_param(a, GainNode::GAIN, 0.5).unwrap();
+        }
+        assert!(matches!(
+            controller.set_param(a, GainNode::GAIN, 0.5),
+            Err(DawError::CommandQueueFull)
+        ));
+        assert_eq!(controller.dropped_commands(), 1);
+        renderer.render_frames(64);
+
+        let later = controller.sample_pos() + 48_000;
+        for _ in 0..4 {
+            controller
+                .set_param_at(a, GainNode::GAIN, 0.5, later)
+                .unwrap();
+        }
+        renderer.render_frames(64);
+        assert_eq!(controller.early_commands(), 0);
+        controller
+            .set_param_at(a, GainNode::GAIN, 0.5, later)
+            .unwrap();
+        renderer.render_frames(64);
+        assert_eq!(controller.early_commands(), 1);
+
+        controller.remove_node(a).unwrap();
+        renderer.render_frames(64);
+        assert_eq!(controller.collect_garbage(), 1);
+        assert!(controller.add_node(Box::new(GainNode::new(1.0))).is_ok());
+    }
+
+    #[test]
+    fn removed_inputs_let_go_of_their_stream() {
+        let (mut controller, mut renderer) = AudioController::offline(DEFAULT_SAMPLE_RATE, 1);
+        let [a, b] = [0, 1].map(|key| {
+            let id = controller
+                .add_node(Box::new(ToneGeneratorNode::new(440.0_f32, 1.0)))
+                .unwrap();
+            controller.inputs.insert(id, key);
+            id
+        });
+
+        controller.remove_node(a).unwrap();
+        assert!(!controller.inputs.contains_key(&a));
+
+        // Still playing until the removal is due.
+        controller
+            .remove_node_at(b, controller.sample_pos() + 100)
+            .unwrap();
+        assert!(controller.inputs.contains_key(&b));
+
+        renderer.render_frames(512);
+        controller.collect_garbage();
+        assert!(controller.inputs.is_empty());
+    }
+
+    #[test]
+    fn empty_limits_still_run() {
+        let limits = AudioLimits {
+            max_nodes: 0,
+            command_queue: 0,
+            pending_commands: 0,
+            events: 0,
+            block_size: 0,
+        };
+        let (mut controller, mut renderer) =
+            AudioController::offline_with_limits(DEFAULT_SAMPLE_RATE, 1, limits);
+
+        assert!(matches!(
+            controller.add_node(Box::new(GainNode::new(1.0))),
+            Err(DawError::NodeTableFull(1))
+        ));
+        controller
+            .set_param(NodeId::MASTER, GainNode::GAIN, 0.5)
+            .unwrap();
+        assert_eq!(renderer.render_frames(64).len(), 64);
+
+        let controller = AudioController::null_with_limits(DEFAULT_SAMPLE_RATE, 1, limits);
+        assert_eq!(controller.profile().len(), 1);
+    }
+
+    #[test]
+    fn engines_run_side_by_side() {
+        let render = |freq: f32| {
+            let (mut controller, mut renderer) = AudioController::offline(DEFAULT_SAMPLE_RATE, 1);
+            let tone = controller
+                .add_node(Box::new(ToneGeneratorNode::new(freq, 1.0)))
+                .unwrap();
+            controller.connect(tone, controller.master()).unwrap();
+
+            renderer.render_frames(4096)
+        };
+
+        let expected = [render(440.0), render(1000.0)];
+
+        let threads = [440.0, 1000.0].map(|freq| std::thread::spawn(move || render(freq)));
+        let rendered = threads.map(|thread| thread.join().unwrap());
+
+        assert_ne!(expected[0], expected[1]);
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn profile_covers_nodes_and_group_members() {
+        let (mut controller, mut renderer) = AudioController::offline(DEFAULT_SAMPLE_RATE, 1);
+        controller.set_profiling(true);
 
-            if let Some(ptr) = NodePtr::new(static_node as *mut dyn AudioNode) {
-                let id = NodeId(self.next_id + 1);
-                let ptr_copy = ptr.clone();
+        let group = GroupNode::new()
+            .add_node(ToneGeneratorNode::new(440.0_f32, 1.0))
+            .add_node(GainNode::new(0.5));
+        let group = controller.add_node(Box::new(group)).unwrap();
+        controller.connect(group, controller.master()).unwrap();
 
-                self.nodes.insert(id, ptr);
-                self.send_command(AudioCommand::AddNode(ptr_copy));
+        renderer.render_frames(1024);
+        let profile = controller.profile();
 
-                self.next_id += 1;
+        assert_eq!(profile.len(), 4);
+        assert!(profile.windows(2).all(|w| w[0].total >= w[1].total));
 
-                return Some(id);
-            } else {
-                let boxed = Box::from_raw(static_node);
-                drop(boxed);
+        let member = profile.iter().find(|p| p.child == Some(0)).unwrap();
+        assert_eq!(member.id, group);
+        assert!(member.name.ends_with("ToneGeneratorNode"));
+        assert!(member.calls > 0);
+
+        controller.set_profiling(false);
+        let calls = member.calls;
+        renderer.render_frames(1024);
+
+        let profile = controller.profile();
+        let member = profile.iter().find(|p| p.child == Some(0)).unwrap();
+        assert_eq!(member.calls, calls);
+    }
+
+    #[test]
+    fn groups_forward_member_events() {
+        let (mut controller, mut renderer) = AudioController::offline(48_000, 1);
+
+        let group = GroupNode::new()
+            .add_node(MeterNode::new(10.0))
+            .add_node(MeterNode::new(10.0));
+        let group = controller.add_node(Box::new(group)).unwrap();
+        renderer.render_frames(48_000);
+
+        let meters = controller
+            .audio_events()
+            .iter()
+            .filter(|event| match event {
+                AudioEvent::Node(AudioNodeEvent { node, event }) => {
+                    *node == group && matches!(event, NodeEvent::Meter { .. })
+                }
+                _ => false,
+            })
+            .count();
+        assert_eq!(meters, 18);
+    }
+
+    #[test]
+    fn audio_thread_reports_events_in_order() {
+        let (mut controller, mut renderer) = AudioController::offline(48_000, 1);
+
+        let meter = controller.add_node(Box::new(MeterNode::new(10.0))).unwrap();
+        controller.send_transport(TransportCommand::Play).unwrap();
+        renderer.render_frames(48_000);
+
+        let events = controller.audio_events();
+        assert!(matches!(
+            events[0],
+            AudioEvent::Transport(TransportChanged(transport)) if transport.is_playing()
+        ));
+
+        // Two beats per second at the default 120 BPM.
+        let beats: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                AudioEvent::Beat(BeatCrossed(time)) => Some(*time),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            beats,
+            [MusicalTime::new(0, 0, 0), MusicalTime::new(0, 1, 0)]
+        );
+
+        let meters = events
+            .iter()
+            .filter(|event| match event {
+                AudioEvent::Node(AudioNodeEvent { node, event }) => {
+                    *node == meter && matches!(event, NodeEvent::Meter { .. })
+                }
+                _ => false,
+            })
+            .count();
+        assert_eq!(meters, 9);
+
+        assert!(controller.audio_events().is_empty());
+    }
+
+    #[test]
+    fn finished_nodes_remove_themselves() {
+        let (mut controller, mut renderer) = AudioController::offline(48_000, 1);
+
+        let tone = controller
+            .add_node(Box::new(ToneGeneratorNode::new(1000.0_f32, 1.0)))
+            .unwrap();
+        let envelope = EnvelopeNode::new(Duration::ZERO, Duration::from_millis(10), Duration::ZERO);
+        let envelope = controller.add_node(Box::new(envelope)).unwrap();
+        controller.connect(tone, envelope).unwrap();
+        controller.connect(envelope, controller.master()).unwrap();
+
+        let output = renderer.render_frames(2048);
+        assert!(output[1..480].iter().all(|s| *s != 0.0));
+        assert!(output[480..].iter().all(|s| *s == 0.0));
+
+        assert!(
+            controller
+                .audio_events()
+                .contains(&AudioEvent::Node(AudioNodeEvent {
+                    node: envelope,
+                    event: NodeEvent::Finished,
+                }))
+        );
+        assert_eq!(controller.collect_garbage(), 1);
+        assert!(controller.params(envelope).is_none());
+        assert!(controller.params(tone).is_some());
+    }
+
+    #[test]
+    fn late_removals_of_finished_nodes_spare_the_next_one() {
+        let (mut controller, mut renderer) = AudioController::offline(48_000, 1);
+
+        let envelope = EnvelopeNode::new(Duration::ZERO, Duration::ZERO, Duration::ZERO);
+        let envelope = controller.add_node(Box::new(envelope)).unwrap();
+        renderer.render_frames(1024);
+
+        // Finished already, the controller only learns on the next collection.
+        let later = controller.sample_pos() + 1000;
+        controller.remove_node_at(envelope, later).unwrap();
+        assert_eq!(controller.collect_garbage(), 1);
+
+        let tone = controller
+            .add_node(Box::new(ToneGeneratorNode::new(1000.0_f32, 1.0)))
+            .unwrap();
+        controller.connect(tone, controller.master()).unwrap();
+
+        let output = renderer.render_frames(2048);
+        assert!(output[1024..].iter().any(|s| *s != 0.0));
+        assert_eq!(controller.collect_garbage(), 0);
+        assert!(controller.params(tone).is_some());
+    }
+
+    #[test]
+    fn finished_garbage_leaves_the_slot_next_node_alone() {
+        let (mut controller, mut renderer) = AudioController::offline(48_000, 1);
+
+        let envelope = EnvelopeNode::new(Duration::ZERO, Duration::ZERO, Duration::ZERO);
+        let envelope = controller.add_node(Box::new(envelope)).unwrap();
+        renderer.render_frames(1024);
+
+        // Removed after it finished, its slot is reused before that is collected.
+        controller.remove_node(envelope).unwrap();
+        let tone = controller
+            .add_node(Box::new(ToneGeneratorNode::new(1000.0_f32, 1.0)))
+            .unwrap();
+        controller
+            .remove_node_at(tone, controller.sample_pos() + 100)
+            .unwrap();
+        assert_eq!(controller.collect_garbage(), 1);
+
+        renderer.render_frames(1024);
+        assert_eq!(controller.collect_garbage(), 1);
+        assert!(controller.params(tone).is_none());
+    }
+
+    #[derive(Debug)]
+    struct Ramp;
+
+    impl AudioNode for Ramp {
+        fn process(&mut self, ctx: &ProcessContext, output: &mut AudioBufferMut) {
+            assert!(output.frames() <= 64);
+
+            for (i, sample) in output.channel_mut(0).iter_mut().enumerate() {
+                *sample = (ctx.sample_pos + i as u64) as f32;
             }
         }
+    }
+
+    #[test]
+    fn callbacks_of_any_size_get_whole_blocks() {
+        let limits = AudioLimits {
+            block_size: 64,
+            ..AudioLimits::default()
+        };
+        let (mut controller, mut renderer) =
+            AudioController::offline_with_limits(DEFAULT_SAMPLE_RATE, 1, limits);
 
-        None
+        let ramp = controller.add_node(Box::new(Ramp)).unwrap();
+        controller.connect(ramp, controller.master()).unwrap();
+
+        // Larger than any buffer the engine used to keep on the stack.
+        let mut output = vec![0.0; MAX_BUFFER_SIZE + 100];
+        let (head, tail) = output.split_at_mut(100);
+        renderer.engine.pull(head, |sample| sample);
+        renderer.engine.pull(tail, |sample| sample);
+
+        assert!(output.iter().enumerate().all(|(i, s)| *s == i as f32));
     }
 
-    pub fn remove_node(&mut self, id: NodeId) {
-        if let Some(ptr) = self.nodes.get(&id) {
-            let boxed: Box<dyn AudioNode> = unsafe { ptr.clone().into_box() };
-            self.send_command(AudioCommand::RemoveNode(ptr.clone()));
-            drop(boxed)
+    #[derive(Debug, Default)]
+    struct Rate(u32);
+
+    impl AudioNode for Rate {
+        fn prepare(&mut self, sample_rate: u32, _max_block_size: usize, _channels: usize) {
+            self.0 = sample_rate;
+        }
+
+        fn process(&mut self, _ctx: &ProcessContext, output: &mut AudioBufferMut) {
+            output.fill(self.0 as f32);
         }
     }
 
-    pub(super) fn send_command(&self, cmd: AudioCommand) {
-        let mut queue = AUDIO_QUEUE.lock();
-        let (mut producer, _) = queue.split();
-        producer.enqueue(cmd).ok();
+    #[test]
+    fn nodes_sent_during_a_format_change_are_prepared_again() {
+        let (mut controller, mut renderer) = AudioController::offline(48_000, 1);
+
+        // Still in the queue when the stream comes back at another rate.
+        let rate = controller.add_node(Box::new(Rate::default())).unwrap();
+        controller.connect(rate, controller.master()).unwrap();
+        renderer.engine.prepare(24_000, 2);
+
+        // A whole block, so the second pull renders a new one.
+        let mut output = vec![0.0; DEFAULT_BLOCK_SIZE * 2];
+        renderer.engine.pull(&mut output, |sample| sample);
+        assert!(output.iter().all(|s| *s == 0.0));
+
+        assert_eq!(controller.collect_garbage(), 0);
+        renderer.engine.pull(&mut output, |sample| sample);
+        assert!(output.iter().all(|s| *s == 24_000.0));
     }
 }
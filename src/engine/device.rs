@@ -1,109 +1,773 @@
-use super::SAMPLE_RATE;
 use crate::AudioController;
-use crate::engine::{AUDIO_STATE, MAX_BUFFER_SIZE};
+use crate::engine::queue::Queue;
+use crate::engine::{AudioEngine, AudioLimits, DEFAULT_CHANNELS, DEFAULT_SAMPLE_RATE};
+use crate::error::DawError;
+use crate::node::NodeId;
+use crate::node::nodes::InputNode;
 use assert_no_alloc::*;
+use bevy::ecs::event::Event;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use hashbrown::HashMap;
+use spin::Mutex;
+use std::sync::{Arc, mpsc};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+const MAX_NOTICES: usize = 64;
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_INPUT_LATENCY: usize = 1024;
 
 #[cfg(debug_assertions)]
 #[global_allocator]
 static A: AllocDisabler = AllocDisabler;
 
-fn audio_loop<S>(data: &mut [S])
+// Anything left out falls back to the default host, device and buffer size.
+// The sample rate and channel count are only preferences, devices that can't
+// do them run as close as they get.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DeviceSelection {
+    pub host: Option<cpal::HostId>,
+    pub device: Option<String>,
+    pub buffer_size: Option<u32>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+}
+
+#[derive(Clone, Debug)]
+pub struct OutputDeviceInfo {
+    pub host: cpal::HostId,
+    pub name: String,
+    pub is_default: bool,
+    pub configs: Vec<OutputConfigInfo>,
+}
+
+#[derive(Clone, Debug)]
+pub struct OutputConfigInfo {
+    pub channels: u16,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+    pub sample_format: cpal::SampleFormat,
+    // `None` if the host can't tell before a stream is running.
+    pub buffer_size: Option<(u32, u32)>,
+}
+
+#[derive(Clone, Debug, Event)]
+pub enum AudioStreamEvent {
+    Error(String),
+    DeviceLost(String),
+    Recovered(String),
+    RecoveryFailed(String),
+    // Its stream was closed along with the output's, the node stays silent
+    // until it's removed.
+    InputClosed(NodeId),
+}
+
+#[derive(Clone, Debug)]
+struct StreamInfo {
+    device: String,
+    sample_rate: u32,
+    channels: usize,
+}
+
+enum StreamMessage {
+    Open(DeviceSelection, mpsc::Sender<Result<StreamInfo, DawError>>),
+    SetFallback(Option<DeviceSelection>),
+    OpenInput(
+        DeviceSelection,
+        mpsc::Sender<Result<(u64, InputNode), DawError>>,
+    ),
+    CloseInput(u64),
+    Pause(mpsc::Sender<Result<(), DawError>>),
+    Resume(mpsc::Sender<Result<(), DawError>>),
+    // Tagged with the stream generation, errors of replaced streams are ignored.
+    Error(u64, cpal::StreamError),
+    Shutdown,
+}
+
+// Picked up by the controller, which has to follow a recovered stream's layout.
+#[derive(Debug)]
+enum StreamNotice {
+    Event(AudioStreamEvent),
+    Reopened(StreamInfo),
+    InputsClosed(Vec<u64>),
+}
+
+// The stream never leaves its thread, some hosts don't allow that.
+#[derive(Debug)]
+pub(super) struct StreamHandle {
+    messages: mpsc::Sender<StreamMessage>,
+    notices: Arc<Queue<StreamNotice>>,
+    thread: Option<thread::JoinHandle<Option<AudioEngine>>>,
+}
+
+impl StreamHandle {
+    fn spawn(engine: AudioEngine) -> Self {
+        let (messages, receiver) = mpsc::channel();
+        let notices = Arc::new(Queue::new(MAX_NOTICES));
+
+        let (sender, shared) = (messages.clone(), notices.clone());
+
+        let thread = thread::spawn(move || {
+            let mut thread = StreamThread {
+                engine: Arc::new(Mutex::new(engine)),
+                messages: sender,
+                notices: shared,
+                current: None,
+                selection: DeviceSelection::default(),
+                fallback: None,
+                generation: 0,
+                lost: false,
+                reported: false,
+                paused: false,
+                inputs: Vec::new(),
+                next_input: 0,
+            };
+
+            thread.run(receiver);
+            thread.into_engine()
+        });
+
+        Self {
+            messages,
+            notices,
+            thread: Some(thread),
+        }
+    }
+
+    fn request<T>(
+        &self,
+        message: impl FnOnce(mpsc::Sender<Result<T, DawError>>) -> StreamMessage,
+    ) -> Result<T, DawError> {
+        let (reply, result) = mpsc::channel();
+
+        self.messages
+            .send(message(reply))
+            .map_err(|_| DawError::NoStream)?;
+
+        result.recv().map_err(|_| DawError::NoStream)?
+    }
+
+    fn open(&self, selection: DeviceSelection) -> Result<StreamInfo, DawError> {
+        self.request(|reply| StreamMessage::Open(selection, reply))
+    }
+
+    // Waits until the stream is closed and hands back the engine with all its nodes.
+    fn shutdown(mut self) -> Option<AudioEngine> {
+        self.messages.send(StreamMessage::Shutdown).ok();
+        self.thread.take()?.join().ok().flatten()
+    }
+}
+
+impl Drop for StreamHandle {
+    fn drop(&mut self) {
+        self.messages.send(StreamMessage::Shutdown).ok();
+    }
+}
+
+struct StreamThread {
+    engine: Arc<Mutex<AudioEngine>>,
+    messages: mpsc::Sender<StreamMessage>,
+    notices: Arc<Queue<StreamNotice>>,
+    current: Option<(cpal::Stream, StreamInfo)>,
+    selection: DeviceSelection,
+    fallback: Option<DeviceSelection>,
+    generation: u64,
+    lost: bool,
+    reported: bool,
+    paused: bool,
+    inputs: Vec<(u64, cpal::Stream)>,
+    next_input: u64,
+}
+
+impl StreamThread {
+    // The output stream's callback holds the only other reference.
+    fn into_engine(self) -> Option<AudioEngine> {
+        drop(self.current);
+        drop(self.inputs);
+        Arc::into_inner(self.engine).map(Mutex::into_inner)
+    }
+
+    fn run(&mut self, messages: mpsc::Receiver<StreamMessage>) {
+        loop {
+            // Keep trying to recover while the device is gone.
+            let message = if self.lost {
+                match messages.recv_timeout(RETRY_INTERVAL) {
+                    Ok(message) => message,
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        self.recover();
+                        continue;
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+            } else {
+                match messages.recv() {
+                    Ok(message) => message,
+                    Err(_) => return,
+                }
+            };
+
+            match message {
+                StreamMessage::Open(selection, reply) => {
+                    reply.send(self.switch(selection)).ok();
+                }
+                StreamMessage::SetFallback(fallback) => self.fallback = fallback,
+                StreamMessage::OpenInput(selection, reply) => {
+                    reply.send(self.open_input(&selection)).ok();
+                }
+                StreamMessage::CloseInput(key) => self.inputs.retain(|(k, _)| *k != key),
+                StreamMessage::Pause(reply) => {
+                    reply.send(self.set_paused(true)).ok();
+                }
+                StreamMessage::Resume(reply) => {
+                    reply.send(self.set_paused(false)).ok();
+                }
+                StreamMessage::Error(generation, err) if generation == self.generation => {
+                    self.on_error(err);
+                }
+                StreamMessage::Error(..) => {}
+                StreamMessage::Shutdown => return,
+            }
+        }
+    }
+
+    // Remembered across device switches and recoveries.
+    fn set_paused(&mut self, paused: bool) -> Result<(), DawError> {
+        if let Some((stream, _)) = &self.current {
+            if paused {
+                stream.pause()?;
+            } else {
+                stream.play()?;
+            }
+        }
+
+        self.paused = paused;
+        Ok(())
+    }
+
+    fn notify(&self, notice: StreamNotice) {
+        // Nobody is listening if the queue is full, dropping is fine.
+        self.notices.push(notice).ok();
+    }
+
+    fn switch(&mut self, selection: DeviceSelection) -> Result<StreamInfo, DawError> {
+        if let Some((stream, _)) = &self.current {
+            stream.pause().ok();
+        }
+
+        match self.open(&selection) {
+            Ok(info) => {
+                self.selection = selection;
+                self.lost = false;
+                Ok(info)
+            }
+            Err(err) => {
+                if let Some((stream, info)) = &self.current {
+                    self.engine.lock().prepare(info.sample_rate, info.channels);
+
+                    if !self.paused {
+                        stream.play().ok();
+                    }
+                }
+
+                Err(err)
+            }
+        }
+    }
+
+    fn on_error(&mut self, err: cpal::StreamError) {
+        match err {
+            cpal::StreamError::DeviceNotAvailable => {
+                let device = self
+                    .current
+                    .take()
+                    .map(|(_, info)| info.device)
+                    .unwrap_or_default();
+
+                self.notify(StreamNotice::Event(AudioStreamEvent::DeviceLost(device)));
+                self.lost = true;
+                self.reported = false;
+                self.recover();
+            }
+            err => self.notify(StreamNotice::Event(AudioStreamEvent::Error(
+                err.to_string(),
+            ))),
+        }
+    }
+
+    // The engine survives in between, so the graph comes back as it was.
+    fn recover(&mut self) {
+        let default = DeviceSelection {
+            device: None,
+            ..self.selection.clone()
+        };
+        let candidates: Vec<_> = self.fallback.iter().cloned().chain([default]).collect();
+        let mut last_err = None;
+
+        for candidate in candidates {
+            match self.open(&candidate) {
+                Ok(info) => {
+                    self.lost = false;
+                    self.notify(StreamNotice::Reopened(info.clone()));
+                    self.notify(StreamNotice::Event(AudioStreamEvent::Recovered(
+                        info.device,
+                    )));
+                    return;
+                }
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        // Only reported once per loss, retries stay quiet.
+        if let Some(err) = last_err
+            && !self.reported
+        {
+            self.reported = true;
+            self.notify(StreamNotice::Event(AudioStreamEvent::RecoveryFailed(
+                err.to_string(),
+            )));
+        }
+    }
+
+    fn open(&mut self, selection: &DeviceSelection) -> Result<StreamInfo, DawError> {
+        let generation = self.generation + 1;
+        let messages = self.messages.clone();
+        let on_error = move |err| {
+            messages.send(StreamMessage::Error(generation, err)).ok();
+        };
+
+        let (stream, info) = open_stream(&self.engine, selection, on_error)?;
+
+        if self.paused {
+            stream.pause()?;
+        }
+
+        self.generation = generation;
+        self.current = Some((stream, info.clone()));
+
+        // Captured at the previous output's rate, they can't follow the new one.
+        if !self.inputs.is_empty() {
+            let keys = self.inputs.drain(..).map(|(key, _)| key).collect();
+            self.notify(StreamNotice::InputsClosed(keys));
+        }
+
+        Ok(info)
+    }
+
+    // Captured at the output rate, there is no resampling in between.
+    fn open_input(&mut self, selection: &DeviceSelection) -> Result<(u64, InputNode), DawError> {
+        let sample_rate = match &self.current {
+            Some((_, info)) => info.sample_rate,
+            None => return Err(DawError::NoStream),
+        };
+
+        let notices = self.notices.clone();
+        let on_error = move |err: cpal::StreamError| {
+            let event = AudioStreamEvent::Error(err.to_string());
+            notices.push(StreamNotice::Event(event)).ok();
+        };
+
+        let (stream, node) = open_input_stream(selection, sample_rate, on_error)?;
+        let key = self.next_input;
+
+        self.next_input += 1;
+        self.inputs.push((key, stream));
+
+        Ok((key, node))
+    }
+}
+
+fn audio_loop<S>(engine: &mut AudioEngine, data: &mut [S])
 where
     S: cpal::Sample + cpal::FromSample<f32>,
 {
     assert_no_alloc(|| {
-        AUDIO_STATE.with(|state| {
-            let buf = &mut [0.0f32; MAX_BUFFER_SIZE];
-            let mut processed = false;
-
-            if let Ok(mut state) = state.try_borrow_mut() {
-                state.process(&mut buf[..data.len()]);
-                processed = true;
-            }
+        let start = Instant::now();
+        engine.pull(data, resample);
 
-            for i in 0..data.len() {
-                let sample = if processed { buf[i] } else { 0.0 };
-                data[i] = resample(sample);
-            }
-        });
+        let frames = data.len() / engine.channels;
+        let budget = Duration::from_secs_f64(frames as f64 / engine.sample_rate as f64);
+        engine.shared.load.record(start.elapsed(), budget);
     });
 }
 
+// The engine is only locked by the main thread while switching devices,
+// the callback plays silence instead of waiting for it.
 macro_rules! build_stream_match {
-    ($device:expr, $config:expr, $state:expr,$err_fn:expr, { $( $fmt:path => $ty:ty ),* $(,)? }) => {
-        match $device.default_output_config().unwrap().sample_format() {
+    ($device:expr, $format:expr, $config:expr, $engine:expr, $shared:expr, $err_fn:expr, { $( $fmt:path => $ty:ty ),* $(,)? }) => {
+        match $format {
+            $(
+                $fmt => {
+                    let engine = $engine.clone();
+                    let shared = $shared.clone();
+
+                    $device.build_output_stream(
+                        $config,
+                        move |data: &mut [$ty], _| match engine.try_lock() {
+                            Some(mut engine) => audio_loop(&mut engine, data),
+                            None => {
+                                data.fill(<$ty as cpal::Sample>::EQUILIBRIUM);
+                                shared.load.underrun();
+                            }
+                        },
+                        $err_fn,
+                        None,
+                    )
+                }
+            )*
+            other => return Err(DawError::UnsupportedSampleFormat(other)),
+        }
+    };
+}
+
+fn open_stream<E>(
+    engine: &Arc<Mutex<AudioEngine>>,
+    selection: &DeviceSelection,
+    on_error: E,
+) -> Result<(cpal::Stream, StreamInfo), DawError>
+where
+    E: FnMut(cpal::StreamError) + Send + 'static,
+{
+    let host = match selection.host {
+        Some(id) => cpal::host_from_id(id)?,
+        None => cpal::default_host(),
+    };
+    let device = match &selection.device {
+        Some(name) => host
+            .output_devices()?
+            .find(|device| device.name().is_ok_and(|n| n == *name))
+            .ok_or_else(|| DawError::DeviceNotFound(name.clone()))?,
+        None => host
+            .default_output_device()
+            .ok_or(DawError::NoOutputDevice)?,
+    };
+
+    let supported = pick_config(&device, selection)?;
+    let channels = supported.channels() as usize;
+    let mut config = supported.config();
+
+    if let Some(size) = selection.buffer_size {
+        let fits = match supported.buffer_size() {
+            cpal::SupportedBufferSize::Range { min, max } => (*min..=*max).contains(&size),
+            cpal::SupportedBufferSize::Unknown => true,
+        };
+
+        if !fits {
+            return Err(DawError::UnsupportedBufferSize(size));
+        }
+
+        config.buffer_size = cpal::BufferSize::Fixed(size);
+    }
+
+    let info = StreamInfo {
+        device: device.name().unwrap_or_default(),
+        sample_rate: config.sample_rate.0,
+        channels,
+    };
+
+    let shared = engine.lock().shared.clone();
+    let stream = build_stream_match!(
+        device,
+        supported.sample_format(),
+        &config,
+        engine,
+        shared,
+        on_error,
+        {
+            cpal::SampleFormat::F32 => f32,
+            cpal::SampleFormat::I16 => i16,
+            cpal::SampleFormat::I24 => cpal::I24,
+            cpal::SampleFormat::I32 => i32,
+            cpal::SampleFormat::I8 => i8,
+            cpal::SampleFormat::U16 => u16,
+            cpal::SampleFormat::U32 => u32,
+            cpal::SampleFormat::U8 => u8,
+        }
+    )?;
+
+    {
+        // Commands still in flight carry nodes prepared for the old device.
+        let mut engine = engine.lock();
+        engine.receive_commands();
+        engine.prepare(info.sample_rate, info.channels);
+    }
+
+    stream.play()?;
+
+    Ok((stream, info))
+}
+
+macro_rules! build_input_match {
+    ($device:expr, $format:expr, $config:expr, $sender:expr, $err_fn:expr, { $( $fmt:path => $ty:ty ),* $(,)? }) => {
+        match $format {
             $(
-                $fmt => $device.build_output_stream(
-                    $config,
-                    |data: &mut [$ty], _| audio_loop(data),
-                    $err_fn,
-                    None,
-                ),
+                $fmt => {
+                    let mut sender = $sender;
+
+                    $device.build_input_stream(
+                        $config,
+                        move |data: &[$ty], _| {
+                            let samples = data.iter().map(|s| cpal::Sample::to_sample::<f32>(*s));
+                            sender.push_iter(samples, data.len());
+                        },
+                        $err_fn,
+                        None,
+                    )
+                }
             )*
-            other => panic!("Unsupported sample format {:?}", other),
+            other => return Err(DawError::UnsupportedSampleFormat(other)),
         }
     };
 }
 
+fn open_input_stream<E>(
+    selection: &DeviceSelection,
+    sample_rate: u32,
+    on_error: E,
+) -> Result<(cpal::Stream, InputNode), DawError>
+where
+    E: FnMut(cpal::StreamError) + Send + 'static,
+{
+    let host = match selection.host {
+        Some(id) => cpal::host_from_id(id)?,
+        None => cpal::default_host(),
+    };
+    let device = match &selection.device {
+        Some(name) => host
+            .input_devices()?
+            .find(|device| device.name().is_ok_and(|n| n == *name))
+            .ok_or_else(|| DawError::DeviceNotFound(name.clone()))?,
+        None => host.default_input_device().ok_or(DawError::NoInputDevice)?,
+    };
+
+    let rate = cpal::SampleRate(sample_rate);
+    let configs: Vec<_> = device
+        .supported_input_configs()?
+        .filter(|c| c.min_sample_rate() <= rate && c.max_sample_rate() >= rate)
+        .collect();
+    let supported = configs
+        .iter()
+        .find(|c| c.sample_format() == cpal::SampleFormat::F32)
+        .or(configs.first())
+        .map(|c| c.with_sample_rate(rate))
+        .ok_or(DawError::NoSupportedConfig)?;
+
+    let mut config = supported.config();
+    let latency = match selection.buffer_size {
+        Some(size) => {
+            config.buffer_size = cpal::BufferSize::Fixed(size);
+            size as usize * 2
+        }
+        None => DEFAULT_INPUT_LATENCY,
+    };
+
+    let (node, sender) = InputNode::new(config.channels as usize, latency);
+
+    let stream = build_input_match!(
+        device,
+        supported.sample_format(),
+        &config,
+        sender,
+        on_error,
+        {
+            cpal::SampleFormat::F32 => f32,
+            cpal::SampleFormat::I16 => i16,
+            cpal::SampleFormat::I24 => cpal::I24,
+            cpal::SampleFormat::I32 => i32,
+            cpal::SampleFormat::I8 => i8,
+            cpal::SampleFormat::U16 => u16,
+            cpal::SampleFormat::U32 => u32,
+            cpal::SampleFormat::U8 => u8,
+        }
+    )?;
+
+    stream.play()?;
+
+    Ok((stream, node))
+}
+
 impl AudioController {
     pub fn new() -> Self {
-        let host = cpal::default_host();
-        let device = host.default_output_device().expect("No output device");
-        let mut supported_configs = device
-            .supported_output_configs()
-            .expect("Error getting configs");
-
-        let config = pick_config(&mut supported_configs);
-        if config.is_none() {
-            return Self {
-                ..Default::default()
-            };
-        }
+        Self::try_new().expect("Failed to start audio engine")
+    }
+
+    pub fn try_new() -> Result<Self, DawError> {
+        Self::try_with_device(DeviceSelection::default(), AudioLimits::default())
+    }
 
-        let stream = build_stream_match!(
-            device,
-            &config.unwrap().into(),
-            state_for_thread,
-            |err| eprintln!("{err}"),
-            {
-                cpal::SampleFormat::F32 => f32,
-                cpal::SampleFormat::I16 => i16,
-                cpal::SampleFormat::I24 => cpal::I24,
-                cpal::SampleFormat::I32 => i32,
-                cpal::SampleFormat::I8 => i8,
-                cpal::SampleFormat::U16 => u16,
-                cpal::SampleFormat::U32 => u32,
-                cpal::SampleFormat::U8 => u8,
+    pub fn try_with_limits(limits: AudioLimits) -> Result<Self, DawError> {
+        Self::try_with_device(DeviceSelection::default(), limits)
+    }
+
+    pub fn try_with_device(
+        selection: DeviceSelection,
+        limits: AudioLimits,
+    ) -> Result<Self, DawError> {
+        let engine = AudioEngine::new(limits);
+        let mut controller = Self::for_engine(&engine);
+
+        let stream = StreamHandle::spawn(engine);
+        let info = stream.open(selection)?;
+
+        controller.stream = Some(stream);
+        controller.apply_stream_info(info);
+
+        Ok(controller)
+    }
+
+    pub fn hosts() -> Vec<cpal::HostId> {
+        cpal::available_hosts()
+    }
+
+    pub fn output_devices(host: cpal::HostId) -> Result<Vec<OutputDeviceInfo>, DawError> {
+        let host = cpal::host_from_id(host)?;
+        let default = host
+            .default_output_device()
+            .and_then(|device| device.name().ok());
+
+        let devices = host
+            .output_devices()?
+            .filter_map(|device| {
+                let name = device.name().ok()?;
+                let configs = device
+                    .supported_output_configs()
+                    .map(|configs| configs.map(config_info).collect())
+                    .unwrap_or_default();
+
+                Some(OutputDeviceInfo {
+                    host: host.id(),
+                    is_default: default.as_ref() == Some(&name),
+                    name,
+                    configs,
+                })
+            })
+            .collect();
+
+        Ok(devices)
+    }
+
+    // Name of the output device, `None` when there is no stream.
+    pub fn device(&self) -> Option<&str> {
+        self.device.as_deref()
+    }
+
+    // Nodes keep running, they are prepared again for the new device.
+    // On failure the previous device keeps playing.
+    // Tried before the default device when the current one goes away.
+    pub fn set_fallback_device(&self, fallback: Option<DeviceSelection>) -> Result<(), DawError> {
+        self.stream
+            .as_ref()
+            .ok_or(DawError::NoStream)?
+            .messages
+            .send(StreamMessage::SetFallback(fallback))
+            .map_err(|_| DawError::NoStream)
+    }
+
+    // Also follows streams rebuilt after a device loss, so call it every frame.
+    pub fn stream_events(&mut self) -> Vec<AudioStreamEvent> {
+        let mut events = Vec::new();
+
+        while let Some(notice) = self.stream.as_ref().and_then(|stream| stream.notices.pop()) {
+            match notice {
+                StreamNotice::Event(event) => events.push(event),
+                StreamNotice::Reopened(info) => self.apply_stream_info(info),
+                StreamNotice::InputsClosed(keys) => self.inputs.retain(|id, key| {
+                    if keys.contains(key) {
+                        events.push(AudioStreamEvent::InputClosed(*id));
+                    }
+
+                    !keys.contains(key)
+                }),
             }
-        )
-        .expect("Failed to build output stream");
+        }
 
-        stream.play().unwrap();
+        events
+    }
 
-        thread::spawn(|| {
-            let _stream = stream;
+    // Adds an `InputNode` fed by the input device, route it like any other node.
+    pub fn open_input(&mut self, selection: DeviceSelection) -> Result<NodeId, DawError> {
+        let stream = self.stream.as_ref().ok_or(DawError::NoStream)?;
+        let messages = stream.messages.clone();
+        let (key, node) = stream.request(|reply| StreamMessage::OpenInput(selection, reply))?;
 
-            loop {
-                thread::sleep(Duration::from_secs(60));
+        match self.add_node(Box::new(node)) {
+            Ok(id) => {
+                self.inputs.insert(id, key);
+                Ok(id)
             }
-        });
+            Err(err) => {
+                messages.send(StreamMessage::CloseInput(key)).ok();
+                Err(err)
+            }
+        }
+    }
 
-        Self {
-            ..Default::default()
+    pub fn close_input(&mut self, id: NodeId) -> Result<(), DawError> {
+        if !self.inputs.contains_key(&id) {
+            return Err(DawError::UnknownNode(id));
+        }
+
+        self.remove_node(id)
+    }
+
+    // However the node went away, its stream has nothing left to feed.
+    pub(super) fn release_input(&mut self, id: NodeId) {
+        if let Some(key) = self.inputs.remove(&id)
+            && let Some(stream) = &self.stream
+        {
+            stream.messages.send(StreamMessage::CloseInput(key)).ok();
         }
     }
+
+    pub fn pause(&self) -> Result<(), DawError> {
+        self.stream
+            .as_ref()
+            .ok_or(DawError::NoStream)?
+            .request(StreamMessage::Pause)
+    }
+
+    pub fn resume(&self) -> Result<(), DawError> {
+        self.stream
+            .as_ref()
+            .ok_or(DawError::NoStream)?
+            .request(StreamMessage::Resume)
+    }
+
+    // Closes the stream for good, the controller keeps working as if nothing
+    // was rendering and nodes are released as they get removed.
+    pub fn shutdown(&mut self) {
+        if let Some(engine) = self.stream.take().and_then(StreamHandle::shutdown) {
+            self.idle = Some(Mutex::new(engine));
+        }
+
+        self.device = None;
+        self.inputs.clear();
+        self.collect_garbage();
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    pub fn switch_device(&mut self, selection: DeviceSelection) -> Result<(), DawError> {
+        let info = self
+            .stream
+            .as_ref()
+            .ok_or(DawError::NoStream)?
+            .open(selection)?;
+        self.apply_stream_info(info);
+
+        Ok(())
+    }
+
+    fn apply_stream_info(&mut self, info: StreamInfo) {
+        self.device = Some(info.device);
+        self.sample_rate = info.sample_rate;
+        self.channels = info.channels;
+    }
 }
 
 impl Default for AudioController {
     fn default() -> Self {
-        Self {
-            next_id: 0,
-            nodes: HashMap::new(),
-        }
+        Self::null(DEFAULT_SAMPLE_RATE, DEFAULT_CHANNELS)
     }
 }
 
@@ -114,22 +778,62 @@ where
     S::from_sample(sample)
 }
 
-fn pick_config(configs: &mut cpal::SupportedOutputConfigs) -> Option<cpal::SupportedStreamConfig> {
-    // Best pick 44.1k and f32
-    if let Some(config) = configs.find(|c| {
-        c.min_sample_rate().0 <= SAMPLE_RATE
-            && c.max_sample_rate().0 >= SAMPLE_RATE
-            && c.sample_format() == cpal::SampleFormat::F32
-    }) {
-        return Some(config.with_sample_rate(cpal::SampleRate(SAMPLE_RATE)));
+fn config_info(config: cpal::SupportedStreamConfigRange) -> OutputConfigInfo {
+    OutputConfigInfo {
+        channels: config.channels(),
+        min_sample_rate: config.min_sample_rate().0,
+        max_sample_rate: config.max_sample_rate().0,
+        sample_format: config.sample_format(),
+        buffer_size: match config.buffer_size() {
+            cpal::SupportedBufferSize::Range { min, max } => Some((*min, *max)),
+            cpal::SupportedBufferSize::Unknown => None,
+        },
     }
+}
+
+fn pick_config(
+    device: &cpal::Device,
+    selection: &DeviceSelection,
+) -> Result<cpal::SupportedStreamConfig, DawError> {
+    let configs: Vec<_> = device.supported_output_configs()?.collect();
+    let rate = match selection.sample_rate {
+        Some(rate) => cpal::SampleRate(rate),
+        None => device
+            .default_output_config()
+            .map(|c| c.sample_rate())
+            .unwrap_or(cpal::SampleRate(DEFAULT_SAMPLE_RATE)),
+    };
+
+    let has_channels = |c: &cpal::SupportedStreamConfigRange| {
+        selection
+            .channels
+            .is_none_or(|channels| c.channels() == channels)
+    };
+    let supports_rate = |c: &&cpal::SupportedStreamConfigRange| {
+        c.min_sample_rate() <= rate && c.max_sample_rate() >= rate && has_channels(c)
+    };
 
-    // at least 44.1k
+    // Best pick the rate and f32
     if let Some(config) = configs
-        .find(|c| c.min_sample_rate().0 <= SAMPLE_RATE && c.max_sample_rate().0 >= SAMPLE_RATE)
+        .iter()
+        .filter(supports_rate)
+        .find(|c| c.sample_format() == cpal::SampleFormat::F32)
     {
-        return Some(config.with_sample_rate(cpal::SampleRate(SAMPLE_RATE)));
+        return Ok(config.with_sample_rate(rate));
+    }
+
+    // at least the rate
+    if let Some(config) = configs.iter().find(supports_rate) {
+        return Ok(config.with_sample_rate(rate));
     }
 
-    None
+    // anything, as close to the rate as it gets
+    configs
+        .into_iter()
+        .min_by_key(|c| !has_channels(c))
+        .map(|c| {
+            let rate = rate.clamp(c.min_sample_rate(), c.max_sample_rate());
+            c.with_sample_rate(rate)
+        })
+        .ok_or(DawError::NoSupportedConfig)
 }
@@ -0,0 +1,25 @@
+use super::transport::{MusicalTime, Transport};
+use crate::node::{NodeEvent, NodeId};
+use bevy::ecs::event::Event;
+
+#[derive(Clone, Copy, Debug, Event, PartialEq)]
+pub struct AudioNodeEvent {
+    pub node: NodeId,
+    pub event: NodeEvent,
+}
+
+// Sent when playback reaches the start of a beat, `beat` is 0 on a new bar.
+#[derive(Clone, Copy, Debug, Event, PartialEq)]
+pub struct BeatCrossed(pub MusicalTime);
+
+// The transport right after a `TransportCommand` was applied.
+#[derive(Clone, Copy, Debug, Event, PartialEq)]
+pub struct TransportChanged(pub Transport);
+
+// Everything the audio thread reports back, in the order it happened.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioEvent {
+    Node(AudioNodeEvent),
+    Beat(BeatCrossed),
+    Transport(TransportChanged),
+}
@@ -0,0 +1,222 @@
+use crate::error::DawError;
+use crate::node::NodeId;
+use std::collections::{BTreeMap, BTreeSet};
+
+pub(super) const MASTER_SLOT: usize = 0;
+
+#[derive(Debug, Default)]
+pub(super) struct Schedule {
+    pub steps: Vec<Step>,
+}
+
+// Every node has a single input port summing all incoming connections
+// and a single output port that can feed any number of nodes.
+#[derive(Debug)]
+pub(super) struct Step {
+    pub slot: usize,
+    pub inputs: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct Graph {
+    slots: BTreeMap<NodeId, usize>,
+    edges: BTreeSet<(NodeId, NodeId)>,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self {
+            slots: BTreeMap::from([(NodeId::MASTER, MASTER_SLOT)]),
+            edges: BTreeSet::new(),
+        }
+    }
+
+    pub fn slot(&self, id: NodeId) -> Option<usize> {
+        self.slots.get(&id).copied()
+    }
+
+    pub fn insert(&mut self, id: NodeId, slot: usize) {
+        self.slots.insert(id, slot);
+    }
+
+    pub fn remove(&mut self, id: NodeId) -> Option<usize> {
+        let slot = self.slots.remove(&id)?;
+        self.edges.retain(|(from, to)| *from != id && *to != id);
+        Some(slot)
+    }
+
+    pub fn connect(&mut self, from: NodeId, to: NodeId) -> Result<(), DawError> {
+        for id in [from, to] {
+            if !self.slots.contains_key(&id) {
+                return Err(DawError::UnknownNode(id));
+            }
+        }
+
+        if from == NodeId::MASTER {
+            return Err(DawError::MasterNode);
+        }
+
+        if from == to || self.reaches(to, from) {
+            return Err(DawError::Cycle(from, to));
+        }
+
+        self.edges.insert((from, to));
+        Ok(())
+    }
+
+    pub fn disconnect(&mut self, from: NodeId, to: NodeId) -> Result<(), DawError> {
+        if !self.edges.remove(&(from, to)) {
+            return Err(DawError::NotConnected(from, to));
+        }
+
+        Ok(())
+    }
+
+    fn reaches(&self, from: NodeId, to: NodeId) -> bool {
+        let mut stack = vec![from];
+        let mut visited = BTreeSet::new();
+
+        while let Some(id) = stack.pop() {
+            if id == to {
+                return true;
+            }
+
+            if visited.insert(id) {
+                stack.extend(self.outputs(id));
+            }
+        }
+
+        false
+    }
+
+    fn outputs(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.edges
+            .range((id, NodeId(0))..=(id, NodeId(u32::MAX)))
+            .map(|(_, to)| *to)
+    }
+
+    // Kahn's algorithm, ties are broken by id so the order is deterministic.
+    pub fn compile(&self) -> Schedule {
+        let mut in_degree: BTreeMap<NodeId, usize> = self.slots.keys().map(|id| (*id, 0)).collect();
+
+        for (_, to) in &self.edges {
+            *in_degree.get_mut(to).unwrap() += 1;
+        }
+
+        let mut ready: BTreeSet<NodeId> = in_degree
+            .iter()
+            .filter(|(_, degree)| **degree == 0)
+            .map(|(id, _)| *id)
+            .collect();
+
+        let mut steps = Vec::with_capacity(self.slots.len());
+
+        while let Some(id) = ready.pop_first() {
+            let mut inputs: Vec<usize> = self
+                .edges
+                .iter()
+                .filter(|(_, to)| *to == id)
+                .map(|(from, _)| self.slots[from])
+                .collect();
+            inputs.sort_unstable();
+
+            steps.push(Step {
+                slot: self.slots[&id],
+                inputs,
+            });
+
+            for to in self.outputs(id) {
+                let degree = in_degree.get_mut(&to).unwrap();
+                *degree -= 1;
+
+                if *degree == 0 {
+                    ready.insert(to);
+                }
+            }
+        }
+
+        Schedule { steps }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Graph, MASTER_SLOT};
+    use crate::error::DawError;
+    use crate::node::NodeId;
+
+    fn graph(nodes: u32) -> Graph {
+        let mut graph = Graph::new();
+
+        for i in 1..=nodes {
+            graph.insert(NodeId(i), i as usize);
+        }
+
+        graph
+    }
+
+    #[test]
+    fn schedule_follows_connections() {
+        let mut graph = graph(3);
+
+        graph.connect(NodeId(3), NodeId(2)).unwrap();
+        graph.connect(NodeId(2), NodeId(1)).unwrap();
+        graph.connect(NodeId(1), NodeId::MASTER).unwrap();
+
+        let schedule = graph.compile();
+        let order: Vec<usize> = schedule.steps.iter().map(|step| step.slot).collect();
+
+        assert_eq!(order, [3, 2, 1, MASTER_SLOT]);
+        assert_eq!(schedule.steps[3].inputs, [1]);
+    }
+
+    #[test]
+    fn parallel_branches_sum_into_master() {
+        let mut graph = graph(2);
+
+        graph.connect(NodeId(1), NodeId::MASTER).unwrap();
+        graph.connect(NodeId(2), NodeId::MASTER).unwrap();
+
+        let schedule = graph.compile();
+        let master = schedule.steps.last().unwrap();
+
+        assert_eq!(master.slot, MASTER_SLOT);
+        assert_eq!(master.inputs, [1, 2]);
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let mut graph = graph(3);
+
+        graph.connect(NodeId(1), NodeId(2)).unwrap();
+        graph.connect(NodeId(2), NodeId(3)).unwrap();
+
+        assert!(matches!(
+            graph.connect(NodeId(3), NodeId(1)),
+            Err(DawError::Cycle(..))
+        ));
+        assert!(matches!(
+            graph.connect(NodeId(1), NodeId(1)),
+            Err(DawError::Cycle(..))
+        ));
+        assert!(matches!(
+            graph.connect(NodeId::MASTER, NodeId(1)),
+            Err(DawError::MasterNode)
+        ));
+    }
+
+    #[test]
+    fn removing_a_node_drops_its_connections() {
+        let mut graph = graph(2);
+
+        graph.connect(NodeId(1), NodeId(2)).unwrap();
+        graph.connect(NodeId(2), NodeId::MASTER).unwrap();
+        graph.remove(NodeId(2));
+
+        assert!(matches!(
+            graph.disconnect(NodeId(1), NodeId(2)),
+            Err(DawError::NotConnected(..))
+        ));
+        assert_eq!(graph.compile().steps.len(), 2);
+    }
+}
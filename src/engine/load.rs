@@ -0,0 +1,75 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DspStats {
+    // Percent of the callback budget spent rendering, `None` if nothing ran.
+    pub load: Option<f64>,
+    // Callbacks that took longer than the audio they produced, since startup.
+    pub overruns: u64,
+    // Callbacks that played silence because the engine was busy, since startup.
+    pub underruns: u64,
+}
+
+// Written by the audio callback, the busy and budget totals are drained by
+// whoever reads them.
+#[derive(Debug, Default)]
+pub(super) struct DspLoad {
+    busy: AtomicU64,
+    budget: AtomicU64,
+    overruns: AtomicU64,
+    underruns: AtomicU64,
+}
+
+impl DspLoad {
+    pub fn record(&self, busy: Duration, budget: Duration) {
+        self.busy
+            .fetch_add(busy.as_nanos() as u64, Ordering::Relaxed);
+        self.budget
+            .fetch_add(budget.as_nanos() as u64, Ordering::Relaxed);
+
+        if busy > budget {
+            self.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn underrun(&self) {
+        self.underruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn take(&self) -> DspStats {
+        let busy = self.busy.swap(0, Ordering::Relaxed);
+        let budget = self.budget.swap(0, Ordering::Relaxed);
+
+        DspStats {
+            load: (budget > 0).then(|| busy as f64 / budget as f64 * 100.0),
+            overruns: self.overruns.load(Ordering::Relaxed),
+            underruns: self.underruns.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DspLoad;
+    use std::time::Duration;
+
+    #[test]
+    fn load_is_drained_and_xruns_add_up() {
+        let load = DspLoad::default();
+        assert_eq!(load.take().load, None);
+
+        load.record(Duration::from_millis(1), Duration::from_millis(4));
+        load.record(Duration::from_millis(5), Duration::from_millis(4));
+        load.underrun();
+
+        let stats = load.take();
+        assert_eq!(stats.load, Some(75.0));
+        assert_eq!(stats.overruns, 1);
+        assert_eq!(stats.underruns, 1);
+
+        let stats = load.take();
+        assert_eq!(stats.load, None);
+        assert_eq!(stats.overruns, 1);
+    }
+}
@@ -0,0 +1,167 @@
+use super::{AudioEngine, AudioLimits};
+use crate::buffer::AudioBuffer;
+use crate::error::DawError;
+use bevy::ecs::resource::Resource;
+use std::path::Path;
+use std::time::Duration;
+
+// Inserted by `DawPlugin` with the offline backend, nothing plays unless it
+// gets rendered.
+#[derive(Debug, Resource)]
+pub struct OfflineRenderer {
+    pub(super) engine: AudioEngine,
+    buffer: AudioBuffer,
+}
+
+impl OfflineRenderer {
+    pub(super) fn new(sample_rate: u32, channels: usize, limits: AudioLimits) -> Self {
+        let mut engine = AudioEngine::new(limits);
+        engine.prepare(sample_rate, channels);
+
+        Self {
+            buffer: AudioBuffer::new(channels, engine.block_size),
+            engine,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.engine.sample_rate
+    }
+
+    pub fn channels(&self) -> usize {
+        self.engine.channels
+    }
+
+    pub fn reset(&mut self) {
+        self.engine.reset();
+    }
+
+    // Interleaved, `output.len()` has to be a multiple of the channel count.
+    pub fn render(&mut self, output: &mut [f32]) {
+        let channels = self.channels();
+
+        // No device to line up with, commands sent between calls land on the next frame.
+        for chunk in output.chunks_mut(self.engine.block_size * channels) {
+            let frames = chunk.len() / channels;
+            let mut buffer = self.buffer.slice_mut(0..frames);
+
+            self.engine.process(&mut buffer);
+
+            for (i, frame) in chunk.chunks_exact_mut(channels).enumerate() {
+                for (ch, sample) in frame.iter_mut().enumerate() {
+                    *sample = buffer.channel(ch)[i];
+                }
+            }
+        }
+    }
+
+    pub fn render_frames(&mut self, frames: usize) -> Vec<f32> {
+        let mut output = vec![0.0; frames * self.channels()];
+        self.render(&mut output);
+        output
+    }
+
+    pub fn render_duration(&mut self, duration: Duration) -> Vec<f32> {
+        let frames = (duration.as_secs_f64() * self.sample_rate() as f64).round() as usize;
+        self.render_frames(frames)
+    }
+
+    pub fn render_to_wav<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        duration: Duration,
+    ) -> Result<(), DawError> {
+        let spec = hound::WavSpec {
+            channels: self.channels() as u16,
+            sample_rate: self.sample_rate(),
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+
+        let mut writer = hound::WavWriter::create(path, spec)?;
+
+        for sample in self.render_duration(duration) {
+            writer.write_sample(sample)?;
+        }
+
+        writer.finalize()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::AudioController;
+    use crate::engine::{DEFAULT_CHANNELS, DEFAULT_SAMPLE_RATE};
+    use crate::node::nodes::{DistortionNode, DistortionType, GainNode, ToneGeneratorNode};
+    use std::time::Duration;
+
+    #[test]
+    fn offline_render_is_deterministic() {
+        let mut renders = Vec::new();
+
+        for _ in 0..2 {
+            let (mut controller, mut renderer) =
+                AudioController::offline(DEFAULT_SAMPLE_RATE, DEFAULT_CHANNELS);
+
+            let tone = controller
+                .add_node(Box::new(ToneGeneratorNode::new(440.0_f32, 1.0)))
+                .unwrap();
+            let dist = controller
+                .add_node(Box::new(DistortionNode::new(
+                    10.0,
+                    0.2,
+                    DistortionType::SoftClip,
+                )))
+                .unwrap();
+            let gain = controller.add_node(Box::new(GainNode::new(2.0))).unwrap();
+
+            controller.connect(tone, dist).unwrap();
+            controller.connect(dist, gain).unwrap();
+            controller.connect(gain, controller.master()).unwrap();
+
+            renders.push(renderer.render_frames(10_000));
+        }
+
+        assert!(renders[0].iter().any(|s| s.abs() > 0.1));
+        assert_eq!(renders[0], renders[1]);
+    }
+
+    #[test]
+    fn render_follows_sample_rate() {
+        let (mut controller, mut renderer) = AudioController::offline(48_000, 1);
+
+        let tone = controller
+            .add_node(Box::new(ToneGeneratorNode::new(1000.0_f32, 1.0)))
+            .unwrap();
+        controller.connect(tone, controller.master()).unwrap();
+
+        let first = renderer.render_duration(Duration::from_millis(10));
+        assert_eq!(first.len(), 480);
+
+        // 1 kHz at 48 kHz completes a cycle every 48 samples
+        for (a, b) in first.iter().zip(&first[48..]) {
+            assert!((a - b).abs() < 1e-3);
+        }
+
+        renderer.reset();
+        assert_eq!(renderer.render_duration(Duration::from_millis(10)), first);
+    }
+
+    #[test]
+    fn render_interleaves_channels() {
+        let (mut controller, mut renderer) = AudioController::offline(DEFAULT_SAMPLE_RATE, 2);
+
+        let tone = controller
+            .add_node(Box::new(ToneGeneratorNode::new(440.0_f32, 1.0)))
+            .unwrap();
+        controller.connect(tone, controller.master()).unwrap();
+
+        let output = renderer.render_frames(1000);
+        assert_eq!(output.len(), 2000);
+
+        for frame in output.chunks_exact(2) {
+            assert_eq!(frame[0], frame[1]);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use crate::node::{AudioNode, NodeId};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// Adds up the time spent in one node, only ever touched with relaxed atomics
+// so it can stay on in development builds.
+#[derive(Debug, Default)]
+pub struct NodeTimer {
+    nanos: AtomicU64,
+    calls: AtomicU64,
+}
+
+impl NodeTimer {
+    pub fn measure<R>(&self, enabled: bool, f: impl FnOnce() -> R) -> R {
+        if !enabled {
+            return f();
+        }
+
+        let start = Instant::now();
+        let result = f();
+
+        self.nanos
+            .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        result
+    }
+
+    pub(super) fn clear(&self) {
+        self.nanos.store(0, Ordering::Relaxed);
+        self.calls.store(0, Ordering::Relaxed);
+    }
+
+    fn read(&self) -> (Duration, u64) {
+        let nanos = self.nanos.load(Ordering::Relaxed);
+        let calls = self.calls.load(Ordering::Relaxed);
+        (Duration::from_nanos(nanos), calls)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeProfile {
+    pub id: NodeId,
+    pub name: &'static str,
+    // Index of a nested node, like a member of a `GroupNode`.
+    pub child: Option<usize>,
+    pub calls: u64,
+    pub total: Duration,
+}
+
+impl NodeProfile {
+    pub fn average(&self) -> Duration {
+        self.total / self.calls.max(1) as u32
+    }
+}
+
+// Taken from the node when it's added, the node itself is out of reach after that.
+#[derive(Debug)]
+pub(super) struct Profiled {
+    name: &'static str,
+    children: Vec<(&'static str, Arc<NodeTimer>)>,
+}
+
+impl Profiled {
+    pub fn new(node: &dyn AudioNode) -> Self {
+        Self {
+            name: node.name(),
+            children: node.child_timers(),
+        }
+    }
+
+    pub fn report(&self, id: NodeId, timer: &NodeTimer, report: &mut Vec<NodeProfile>) {
+        let (total, calls) = timer.read();
+
+        report.push(NodeProfile {
+            id,
+            name: self.name,
+            child: None,
+            calls,
+            total,
+        });
+
+        for (i, (name, timer)) in self.children.iter().enumerate() {
+            let (total, calls) = timer.read();
+
+            report.push(NodeProfile {
+                id,
+                name,
+                child: Some(i),
+                calls,
+                total,
+            });
+        }
+    }
+}
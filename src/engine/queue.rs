@@ -0,0 +1,216 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Bounded array queue after Dmitry Vyukov's design. Every cell carries a
+// sequence number telling whose turn it is, so neither side ever waits on
+// the other: a push fails when the queue is full, a pop returns `None` when
+// the next item isn't published yet. Storage is allocated up front.
+pub(super) struct Queue<T> {
+    cells: Box<[Cell<T>]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+struct Cell<T> {
+    seq: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// Values only move through a cell while its sequence number grants exclusive access.
+unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send> Sync for Queue<T> {}
+
+impl<T> Queue<T> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+
+        let cells = (0..capacity)
+            .map(|i| Cell {
+                seq: AtomicUsize::new(i),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .collect();
+
+        Self {
+            cells,
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    fn cell(&self, pos: usize) -> &Cell<T> {
+        &self.cells[pos % self.cells.len()]
+    }
+
+    pub fn push(&self, item: T) -> Result<(), T> {
+        self.push_all([item]).map_err(|[item]| item)
+    }
+
+    // All or nothing, so related items never get split up.
+    pub fn push_all<const N: usize>(&self, batch: [T; N]) -> Result<(), [T; N]> {
+        if N > self.cells.len() {
+            return Err(batch);
+        }
+
+        let mut tail = self.tail.load(Ordering::Relaxed);
+
+        loop {
+            // A free cell stays free until the producer owning its position writes it,
+            // so checking the whole range before claiming it is enough.
+            let free = (0..N).all(|i| {
+                self.cell(tail.wrapping_add(i)).seq.load(Ordering::Acquire) == tail.wrapping_add(i)
+            });
+
+            if !free {
+                let current = self.tail.load(Ordering::Relaxed);
+
+                if current == tail {
+                    return Err(batch);
+                }
+
+                tail = current;
+                continue;
+            }
+
+            match self.tail.compare_exchange_weak(
+                tail,
+                tail.wrapping_add(N),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(current) => tail = current,
+            }
+        }
+
+        for (i, item) in batch.into_iter().enumerate() {
+            let pos = tail.wrapping_add(i);
+            let cell = self.cell(pos);
+
+            unsafe { (*cell.value.get()).write(item) };
+            cell.seq.store(pos.wrapping_add(1), Ordering::Release);
+        }
+
+        Ok(())
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let mut head = self.head.load(Ordering::Relaxed);
+
+        loop {
+            let cell = self.cell(head);
+            let seq = cell.seq.load(Ordering::Acquire);
+
+            if seq != head.wrapping_add(1) {
+                let current = self.head.load(Ordering::Relaxed);
+
+                if current == head {
+                    return None;
+                }
+
+                head = current;
+                continue;
+            }
+
+            match self.head.compare_exchange_weak(
+                head,
+                head.wrapping_add(1),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    let item = unsafe { (*cell.value.get()).assume_init_read() };
+                    cell.seq
+                        .store(head.wrapping_add(self.cells.len()), Ordering::Release);
+
+                    return Some(item);
+                }
+                Err(current) => head = current,
+            }
+        }
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T> fmt::Debug for Queue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Queue")
+            .field("capacity", &self.cells.len())
+            .field("head", &self.head.load(Ordering::Relaxed))
+            .field("tail", &self.tail.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Queue;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn batches_are_all_or_nothing() {
+        let queue = Queue::new(3);
+
+        assert!(queue.push_all([1, 2]).is_ok());
+        assert_eq!(queue.push_all([3, 4]), Err([3, 4]));
+        assert!(queue.push(3).is_ok());
+        assert_eq!(queue.push(4), Err(4));
+
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.pop(), Some(2));
+        assert_eq!(queue.pop(), Some(3));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn producers_never_lose_or_split_batches() {
+        const PRODUCERS: usize = 4;
+        const BATCHES: usize = 10_000;
+
+        let queue = Arc::new(Queue::new(64));
+
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|producer| {
+                let queue = queue.clone();
+
+                thread::spawn(move || {
+                    for batch in 0..BATCHES {
+                        let item = (producer, batch);
+                        while queue.push_all([item, item]).is_err() {
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut next = [0; PRODUCERS];
+        let mut received = 0;
+
+        while received < PRODUCERS * BATCHES * 2 {
+            let Some((producer, batch)) = queue.pop() else {
+                thread::yield_now();
+                continue;
+            };
+
+            // Every item arrives exactly once and in order for each producer.
+            assert_eq!(batch, next[producer] / 2);
+            next[producer] += 1;
+            received += 1;
+        }
+
+        for producer in producers {
+            producer.join().unwrap();
+        }
+
+        assert_eq!(queue.pop(), None);
+    }
+}
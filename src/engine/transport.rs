@@ -0,0 +1,289 @@
+use super::DEFAULT_SAMPLE_RATE;
+use bevy::ecs::resource::Resource;
+
+pub const TICKS_PER_BEAT: u32 = 960;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TimeSignature {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self {
+            numerator: 4,
+            denominator: 4,
+        }
+    }
+}
+
+// Zero based, bar 0 beat 0 tick 0 is the start of the timeline.
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub struct MusicalTime {
+    pub bar: u32,
+    pub beat: u32,
+    pub tick: u32,
+}
+
+impl MusicalTime {
+    pub fn new(bar: u32, beat: u32, tick: u32) -> Self {
+        Self { bar, beat, tick }
+    }
+}
+
+// Half open, playback jumps back to `start` when it reaches `end`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LoopRegion {
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransportCommand {
+    Play,
+    Pause,
+    Stop,
+    Seek(u64),
+    SetTempo(f64),
+    SetTimeSignature(TimeSignature),
+    SetLoop(Option<LoopRegion>),
+}
+
+// Positions are in samples on the transport timeline, which only moves while playing.
+#[derive(Clone, Copy, Debug, PartialEq, Resource)]
+pub struct Transport {
+    playing: bool,
+    position: u64,
+    tempo: f64,
+    time_signature: TimeSignature,
+    loop_region: Option<LoopRegion>,
+    sample_rate: u32,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::new(DEFAULT_SAMPLE_RATE)
+    }
+}
+
+impl Transport {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            playing: false,
+            position: 0,
+            tempo: 120.0,
+            time_signature: TimeSignature::default(),
+            loop_region: None,
+            sample_rate,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn tempo(&self) -> f64 {
+        self.tempo
+    }
+
+    pub fn time_signature(&self) -> TimeSignature {
+        self.time_signature
+    }
+
+    pub fn loop_region(&self) -> Option<LoopRegion> {
+        self.loop_region
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn seconds(&self) -> f64 {
+        self.samples_to_seconds(self.position)
+    }
+
+    pub fn musical_time(&self) -> MusicalTime {
+        self.samples_to_musical(self.position)
+    }
+
+    pub fn samples_to_seconds(&self, samples: u64) -> f64 {
+        samples as f64 / self.sample_rate as f64
+    }
+
+    pub fn seconds_to_samples(&self, seconds: f64) -> u64 {
+        (seconds * self.sample_rate as f64).round().max(0.0) as u64
+    }
+
+    // A beat is one `denominator` note, the tempo counts those per minute.
+    pub fn samples_per_beat(&self) -> f64 {
+        self.sample_rate as f64 * 60.0 / self.tempo
+    }
+
+    pub fn samples_to_beats(&self, samples: u64) -> f64 {
+        samples as f64 / self.samples_per_beat()
+    }
+
+    pub fn beats_to_samples(&self, beats: f64) -> u64 {
+        (beats * self.samples_per_beat()).round().max(0.0) as u64
+    }
+
+    pub fn samples_to_musical(&self, samples: u64) -> MusicalTime {
+        let ticks = (self.samples_to_beats(samples) * TICKS_PER_BEAT as f64) as u64;
+        let beats = ticks / TICKS_PER_BEAT as u64;
+        let beats_per_bar = self.time_signature.numerator as u64;
+
+        MusicalTime {
+            bar: (beats / beats_per_bar) as u32,
+            beat: (beats % beats_per_bar) as u32,
+            tick: (ticks % TICKS_PER_BEAT as u64) as u32,
+        }
+    }
+
+    pub fn musical_to_samples(&self, time: MusicalTime) -> u64 {
+        let beats = time.bar as f64 * self.time_signature.numerator as f64
+            + time.beat as f64
+            + time.tick as f64 / TICKS_PER_BEAT as f64;
+
+        self.beats_to_samples(beats)
+    }
+
+    pub(super) fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub(super) fn apply(&mut self, cmd: TransportCommand) {
+        match cmd {
+            TransportCommand::Play => self.playing = true,
+            TransportCommand::Pause => self.playing = false,
+            TransportCommand::Stop => {
+                self.playing = false;
+                self.position = 0;
+            }
+            TransportCommand::Seek(position) => self.position = position,
+            // NaN would survive the clamp and stall every beat calculation.
+            TransportCommand::SetTempo(tempo) if tempo.is_finite() => {
+                self.tempo = tempo.clamp(1.0, 999.0)
+            }
+            TransportCommand::SetTempo(_) => {}
+            TransportCommand::SetTimeSignature(time_signature) => {
+                self.time_signature = TimeSignature {
+                    numerator: time_signature.numerator.max(1),
+                    denominator: time_signature.denominator.max(1),
+                }
+            }
+            TransportCommand::SetLoop(region) => {
+                self.loop_region = region.filter(|region| region.start < region.end)
+            }
+        }
+    }
+
+    // Frames left until the loop wraps, the engine never renders across it.
+    pub(super) fn frames_until_wrap(&self) -> Option<u64> {
+        match self.loop_region {
+            Some(region) if self.playing && self.position < region.end => {
+                Some(region.end - self.position)
+            }
+            _ => None,
+        }
+    }
+
+    // Beats starting within the next `frames`, counted from the timeline start.
+    pub(super) fn beats_within(&self, frames: u64) -> impl Iterator<Item = u64> {
+        let first = self.samples_to_beats(self.position) as u64;
+        let end = self.position + if self.playing { frames } else { 0 };
+
+        (first..)
+            .skip_while(|beat| self.beats_to_samples(*beat as f64) < self.position)
+            .take_while(move |beat| self.beats_to_samples(*beat as f64) < end)
+    }
+
+    pub(super) fn beat_to_musical(&self, beat: u64) -> MusicalTime {
+        let beats_per_bar = self.time_signature.numerator as u64;
+        MusicalTime::new(
+            (beat / beats_per_bar) as u32,
+            (beat % beats_per_bar) as u32,
+            0,
+        )
+    }
+
+    pub(super) fn advance(&mut self, frames: u64) {
+        if !self.playing {
+            return;
+        }
+
+        self.position += frames;
+
+        if let Some(region) = self.loop_region
+            && self.position == region.end
+        {
+            self.position = region.start;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        LoopRegion, MusicalTime, TICKS_PER_BEAT, TimeSignature, Transport, TransportCommand,
+    };
+
+    #[test]
+    fn musical_time_round_trips() {
+        let mut transport = Transport::new(48_000);
+        transport.apply(TransportCommand::SetTempo(120.0));
+        transport.apply(TransportCommand::SetTimeSignature(TimeSignature {
+            numerator: 3,
+            denominator: 4,
+        }));
+
+        assert_eq!(transport.samples_per_beat(), 24_000.0);
+
+        let time = MusicalTime::new(2, 1, TICKS_PER_BEAT / 2);
+        let samples = transport.musical_to_samples(time);
+
+        assert_eq!(samples, (2 * 3 + 1) * 24_000 + 12_000);
+        assert_eq!(transport.samples_to_musical(samples), time);
+        assert_eq!(transport.samples_to_seconds(samples), 3.75);
+    }
+
+    #[test]
+    fn loop_wraps_at_its_end() {
+        let mut transport = Transport::new(48_000);
+        transport.apply(TransportCommand::SetLoop(Some(LoopRegion {
+            start: 100,
+            end: 200,
+        })));
+
+        transport.advance(150);
+        assert_eq!(transport.position(), 0);
+
+        transport.apply(TransportCommand::Play);
+        transport.apply(TransportCommand::Seek(150));
+        assert_eq!(transport.frames_until_wrap(), Some(50));
+
+        transport.advance(50);
+        assert_eq!(transport.position(), 100);
+
+        transport.apply(TransportCommand::Stop);
+        assert!(!transport.is_playing());
+        assert_eq!(transport.position(), 0);
+    }
+
+    #[test]
+    fn non_finite_tempos_are_ignored() {
+        let mut transport = Transport::new(48_000);
+        transport.apply(TransportCommand::Play);
+
+        for tempo in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            transport.apply(TransportCommand::SetTempo(tempo));
+        }
+
+        assert_eq!(transport.samples_per_beat(), 24_000.0);
+        assert_eq!(transport.beats_within(48_000).count(), 2);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::NodeId;
+use crate::node::param::ParamId;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum DawError {
+    HostUnavailable(cpal::HostUnavailable),
+    Devices(cpal::DevicesError),
+    NoOutputDevice,
+    NoInputDevice,
+    DeviceNotFound(String),
+    SupportedConfigs(cpal::SupportedStreamConfigsError),
+    NoSupportedConfig,
+    UnsupportedSampleFormat(cpal::SampleFormat),
+    UnsupportedBufferSize(u32),
+    BuildStream(cpal::BuildStreamError),
+    PlayStream(cpal::PlayStreamError),
+    PauseStream(cpal::PauseStreamError),
+    NoStream,
+    Wav(hound::Error),
+    UnknownNode(NodeId),
+    MasterNode,
+    Cycle(NodeId, NodeId),
+    NotConnected(NodeId, NodeId),
+    UnknownParam(NodeId, ParamId),
+    CommandQueueFull,
+    NodeTableFull(usize),
+}
+
+impl fmt::Display for DawError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HostUnavailable(err) => write!(f, "audio host unavailable: {err}"),
+            Self::Devices(err) => write!(f, "failed to list output devices: {err}"),
+            Self::NoOutputDevice => write!(f, "no output device available"),
+            Self::NoInputDevice => write!(f, "no input device available"),
+            Self::DeviceNotFound(name) => write!(f, "no device named {name:?}"),
+            Self::SupportedConfigs(err) => write!(f, "failed to query output configs: {err}"),
+            Self::NoSupportedConfig => write!(f, "no supported output config"),
+            Self::UnsupportedSampleFormat(format) => {
+                write!(f, "unsupported sample format {format:?}")
+            }
+            Self::UnsupportedBufferSize(size) => write!(f, "unsupported buffer size {size}"),
+            Self::BuildStream(err) => write!(f, "failed to build output stream: {err}"),
+            Self::PlayStream(err) => write!(f, "failed to start output stream: {err}"),
+            Self::PauseStream(err) => write!(f, "failed to pause output stream: {err}"),
+            Self::NoStream => write!(f, "no output stream is running"),
+            Self::Wav(err) => write!(f, "wav error: {err}"),
+            Self::UnknownNode(id) => write!(f, "unknown node {id:?}"),
+            Self::MasterNode => write!(f, "the master node can't be removed or routed"),
+            Self::Cycle(from, to) => write!(f, "connecting {from:?} to {to:?} creates a cycle"),
+            Self::NotConnected(from, to) => write!(f, "{from:?} is not connected to {to:?}"),
+            Self::UnknownParam(id, param) => write!(f, "{id:?} has no parameter {param:?}"),
+            Self::CommandQueueFull => write!(f, "audio command queue is full"),
+            Self::NodeTableFull(max) => write!(f, "node table is full ({max} nodes)"),
+        }
+    }
+}
+
+impl std::error::Error for DawError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::HostUnavailable(err) => Some(err),
+            Self::Devices(err) => Some(err),
+            Self::SupportedConfigs(err) => Some(err),
+            Self::BuildStream(err) => Some(err),
+            Self::PlayStream(err) => Some(err),
+            Self::PauseStream(err) => Some(err),
+            Self::Wav(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<cpal::HostUnavailable> for DawError {
+    fn from(err: cpal::HostUnavailable) -> Self {
+        Self::HostUnavailable(err)
+    }
+}
+
+impl From<cpal::DevicesError> for DawError {
+    fn from(err: cpal::DevicesError) -> Self {
+        Self::Devices(err)
+    }
+}
+
+impl From<cpal::SupportedStreamConfigsError> for DawError {
+    fn from(err: cpal::SupportedStreamConfigsError) -> Self {
+        Self::SupportedConfigs(err)
+    }
+}
+
+impl From<cpal::BuildStreamError> for DawError {
+    fn from(err: cpal::BuildStreamError) -> Self {
+        Self::BuildStream(err)
+    }
+}
+
+impl From<cpal::PlayStreamError> for DawError {
+    fn from(err: cpal::PlayStreamError) -> Self {
+        Self::PlayStream(err)
+    }
+}
+
+impl From<cpal::PauseStreamError> for DawError {
+    fn from(err: cpal::PauseStreamError) -> Self {
+        Self::PauseStream(err)
+    }
+}
+
+impl From<hound::Error> for DawError {
+    fn from(err: hound::Error) -> Self {
+        Self::Wav(err)
+    }
+}
@@ -1,20 +1,252 @@
-use bevy::app::Plugin;
+use asset::AudioClipLoader;
+use bevy::app::{AppExit, First, Last, Plugin};
+use bevy::asset::{AssetApp, AssetServer};
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::ecs::event::{EventReader, EventWriter};
+use bevy::ecs::schedule::IntoScheduleConfigs;
+use bevy::ecs::system::{Res, ResMut};
+use engine::{DEFAULT_CHANNELS, DEFAULT_SAMPLE_RATE};
 
+mod asset;
+mod buffer;
+mod ecs;
 mod engine;
+mod error;
 mod node;
 mod utils;
 
-pub struct DawPlugin;
+// Where the engine's output goes. `Offline` inserts the `OfflineRenderer` as a
+// resource, `Null` renders nothing but still takes commands.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum AudioBackend {
+    #[default]
+    Device,
+    Offline,
+    Null,
+}
+
+// Left out settings fall back to the device's defaults, or the engine's for
+// the other backends.
+#[derive(Clone, Debug, Default)]
+pub struct DawPlugin {
+    backend: AudioBackend,
+    sample_rate: Option<u32>,
+    channels: Option<usize>,
+    limits: AudioLimits,
+    device: Option<String>,
+    start_paused: bool,
+}
 
-pub use engine::AudioController;
-pub use node::NodeId;
+pub use asset::AudioClip;
+pub use buffer::{AudioBuffer, AudioBufferMut, AudioBufferRef};
+pub use ecs::{
+    Attenuation, AudioEmitter, AudioListener, Cone, DawNode, DawNodeId, DespawnOnFinish,
+};
+pub use engine::{
+    AudioController, AudioEvent, AudioLimits, AudioNodeEvent, AudioStatus, AudioStreamEvent,
+    BeatCrossed, DeviceSelection, DspStats, LoopRegion, MusicalTime, NodeProfile, NodeTimer,
+    OfflineRenderer, OutputConfigInfo, OutputDeviceInfo, TICKS_PER_BEAT, TimeSignature, Transport,
+    TransportChanged, TransportCommand,
+};
+pub use error::DawError;
 pub use node::nodes;
+pub use node::param::{ParamId, ParamInfo, ParamUnit, SmoothedParam};
+pub use node::{NodeEvent, NodeId, ProcessContext};
 
 pub use utils::MidiNote;
 
 impl Plugin for DawPlugin {
     fn build(&self, app: &mut bevy::app::App) {
-        app.insert_resource(AudioController::new());
+        let (controller, status) = self.start(app);
+
+        // A stream that won't pause still plays, which beats not having one.
+        let paused = if self.start_paused && controller.is_running() {
+            controller.pause()
+        } else {
+            Ok(())
+        };
+
+        app.insert_resource(controller.transport())
+            .insert_resource(controller)
+            .insert_resource(status)
+            .add_event::<AudioStreamEvent>()
+            .add_event::<AudioNodeEvent>()
+            .add_event::<BeatCrossed>()
+            .add_event::<TransportChanged>()
+            .register_diagnostic(Diagnostic::new(Self::DSP_LOAD).with_suffix("%"))
+            .register_diagnostic(Diagnostic::new(Self::OVERRUNS).with_smoothing_factor(0.0))
+            .register_diagnostic(Diagnostic::new(Self::UNDERRUNS).with_smoothing_factor(0.0))
+            .add_systems(
+                First,
+                (
+                    forward_stream_events,
+                    forward_audio_events,
+                    sync_transport,
+                    measure_dsp_load,
+                ),
+            )
+            .add_observer(ecs::remove_node)
+            .add_systems(
+                Last,
+                (
+                    ecs::finish_nodes,
+                    ecs::add_nodes,
+                    ecs::route_nodes,
+                    ecs::spatialize,
+                    collect_garbage,
+                    shutdown_on_exit,
+                )
+                    .chain(),
+            );
+
+        if let Err(err) = paused {
+            app.world_mut()
+                .send_event(AudioStreamEvent::Error(err.to_string()));
+        }
+    }
+
+    // Headless apps without an `AssetPlugin` just can't load clips.
+    fn finish(&self, app: &mut bevy::app::App) {
+        if !app.world().contains_resource::<AssetServer>() {
+            return;
+        }
+
+        let controller = app.world().resource::<AudioController>();
+        let loader = AudioClipLoader {
+            sample_rate: controller.sample_rate(),
+            channels: controller.channels(),
+        };
+
+        app.init_asset::<AudioClip>().register_asset_loader(loader);
+    }
+}
+
+impl DawPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_backend(mut self, backend: AudioBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn with_sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    pub fn with_channels(mut self, channels: usize) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.limits.block_size = block_size;
+        self
+    }
+
+    // Replaces everything set by `with_block_size` too.
+    pub fn with_limits(mut self, limits: AudioLimits) -> Self {
+        self.limits = limits.clamped();
+        self
+    }
+
+    pub fn with_device(mut self, device: impl Into<String>) -> Self {
+        self.device = Some(device.into());
+        self
+    }
+
+    // The stream is opened but stays quiet until `AudioController::resume`.
+    // Failing to pause is sent as an `AudioStreamEvent::Error`.
+    pub fn start_paused(mut self, paused: bool) -> Self {
+        self.start_paused = paused;
+        self
+    }
+
+    fn start(&self, app: &mut bevy::app::App) -> (AudioController, AudioStatus) {
+        let sample_rate = self.sample_rate.unwrap_or(DEFAULT_SAMPLE_RATE);
+        let channels = self.channels.unwrap_or(DEFAULT_CHANNELS);
+        let null = || AudioController::null_with_limits(sample_rate, channels, self.limits);
+
+        match self.backend {
+            AudioBackend::Device => {
+                let selection = DeviceSelection {
+                    device: self.device.clone(),
+                    sample_rate: self.sample_rate,
+                    channels: self.channels.map(|channels| channels as u16),
+                    ..DeviceSelection::default()
+                };
+
+                match AudioController::try_with_device(selection, self.limits) {
+                    Ok(controller) => (controller, AudioStatus::Live),
+                    Err(err) => (null(), AudioStatus::Degraded(err)),
+                }
+            }
+            AudioBackend::Offline => {
+                let (controller, renderer) =
+                    AudioController::offline_with_limits(sample_rate, channels, self.limits);
+                app.insert_resource(renderer);
+                (controller, AudioStatus::Live)
+            }
+            AudioBackend::Null => (null(), AudioStatus::Live),
+        }
+    }
+
+    pub const DSP_LOAD: DiagnosticPath = DiagnosticPath::const_new("audio/dsp_load");
+    pub const OVERRUNS: DiagnosticPath = DiagnosticPath::const_new("audio/overruns");
+    pub const UNDERRUNS: DiagnosticPath = DiagnosticPath::const_new("audio/underruns");
+}
+
+fn measure_dsp_load(controller: Res<AudioController>, mut diagnostics: Diagnostics) {
+    let stats = controller.dsp_stats();
+
+    if let Some(load) = stats.load {
+        diagnostics.add_measurement(&DawPlugin::DSP_LOAD, || load);
+    }
+    diagnostics.add_measurement(&DawPlugin::OVERRUNS, || stats.overruns as f64);
+    diagnostics.add_measurement(&DawPlugin::UNDERRUNS, || stats.underruns as f64);
+}
+
+fn forward_stream_events(
+    mut controller: ResMut<AudioController>,
+    mut events: EventWriter<AudioStreamEvent>,
+) {
+    events.write_batch(controller.stream_events());
+}
+
+fn forward_audio_events(
+    controller: Res<AudioController>,
+    mut nodes: EventWriter<AudioNodeEvent>,
+    mut beats: EventWriter<BeatCrossed>,
+    mut transport: EventWriter<TransportChanged>,
+) {
+    for event in controller.audio_events() {
+        match event {
+            AudioEvent::Node(event) => {
+                nodes.write(event);
+            }
+            AudioEvent::Beat(event) => {
+                beats.write(event);
+            }
+            AudioEvent::Transport(event) => {
+                transport.write(event);
+            }
+        }
+    }
+}
+
+fn sync_transport(controller: Res<AudioController>, mut transport: ResMut<Transport>) {
+    *transport = controller.transport();
+}
+
+fn collect_garbage(mut controller: ResMut<AudioController>) {
+    controller.collect_garbage();
+}
+
+fn shutdown_on_exit(mut exit: EventReader<AppExit>, mut controller: ResMut<AudioController>) {
+    if exit.read().next().is_some() {
+        controller.shutdown();
     }
 }
 
@@ -22,3 +254,59 @@ pub mod traits {
     pub use super::node::AudioNode;
     pub use super::utils::Note;
 }
+
+#[cfg(test)]
+mod test {
+    use super::{AudioBackend, AudioController, AudioLimits, DawNode, DawPlugin, OfflineRenderer};
+    use crate::nodes::ToneGeneratorNode;
+    use bevy::app::App;
+
+    #[test]
+    fn offline_backend_runs_headless() {
+        let mut app = App::new();
+        app.add_plugins(
+            DawPlugin::new()
+                .with_backend(AudioBackend::Offline)
+                .with_sample_rate(1000)
+                .with_channels(1)
+                .with_block_size(16),
+        );
+        app.finish();
+
+        let controller = app.world().resource::<AudioController>();
+        assert_eq!((controller.sample_rate(), controller.channels()), (1000, 1));
+
+        app.world_mut()
+            .spawn(DawNode::new(ToneGeneratorNode::new(100.0_f32, 1.0)));
+        app.update();
+
+        let mut renderer = app.world_mut().resource_mut::<OfflineRenderer>();
+        assert!(renderer.render_frames(32).iter().any(|s| *s != 0.0));
+    }
+
+    #[test]
+    fn null_backend_keeps_taking_commands() {
+        let limits = AudioLimits {
+            command_queue: 8,
+            ..AudioLimits::default()
+        };
+
+        let mut app = App::new();
+        app.add_plugins(
+            DawPlugin::new()
+                .with_backend(AudioBackend::Null)
+                .with_limits(limits),
+        );
+        app.finish();
+
+        let mut controller = app.world_mut().resource_mut::<AudioController>();
+
+        for _ in 0..limits.command_queue * 4 {
+            let id = controller
+                .add_node(Box::new(ToneGeneratorNode::new(100.0_f32, 1.0)))
+                .unwrap();
+            controller.remove_node(id).unwrap();
+            assert_eq!(controller.collect_garbage(), 1);
+        }
+    }
+}
@@ -1,25 +1,86 @@
+use crate::buffer::AudioBufferMut;
+use crate::engine::{NodeTimer, Transport};
+use param::{ParamId, ParamInfo};
 use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
 
+mod clip;
 mod delay;
 mod distortion;
+mod envelope;
 mod gain;
 mod group;
+mod input;
+mod meter;
+pub mod param;
+mod spatial;
 mod tone;
 
 #[cfg(test)]
 mod test_utils;
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct NodeId(pub(crate) u32);
 
+impl NodeId {
+    pub const MASTER: Self = Self(0);
+}
+
+// `sample_pos` is the engine clock at the first frame of `output`, it never
+// stops or jumps, unlike the transport. Nodes running nested nodes time them
+// while `profiling` is set.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProcessContext {
+    pub sample_pos: u64,
+    pub transport: Transport,
+    pub profiling: bool,
+}
+
+// Polled by the engine after every `process` call, see `AudioNodeEvent`.
+// `Finished` is sent by the engine when it removes a finished node.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeEvent {
+    Finished,
+    Looped,
+    Meter { peak: f32, rms: f32 },
+}
+
 pub trait AudioNode: Debug + Send + Sync {
-    fn process(&mut self, sample_pos: u32, output: &mut [f32]);
+    fn prepare(&mut self, _sample_rate: u32, _max_block_size: usize, _channels: usize) {}
+    fn reset(&mut self) {}
+    fn params(&self) -> &[ParamInfo] {
+        &[]
+    }
+    fn set_param(&mut self, _id: ParamId, _value: f32) {}
+    fn process(&mut self, ctx: &ProcessContext, output: &mut AudioBufferMut);
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+    fn child_timers(&self) -> Vec<(&'static str, Arc<NodeTimer>)> {
+        Vec::new()
+    }
+    fn take_event(&mut self) -> Option<NodeEvent> {
+        None
+    }
+    // Once finished the node keeps playing for `tail`, then the engine removes it.
+    fn is_finished(&self) -> bool {
+        false
+    }
+    fn tail(&self) -> Duration {
+        Duration::ZERO
+    }
 }
 
 pub mod nodes {
+    pub use super::clip::*;
     pub use super::delay::*;
     pub use super::distortion::*;
+    pub use super::envelope::*;
     pub use super::gain::*;
     pub use super::group::*;
+    pub use super::input::*;
+    pub use super::meter::*;
+    pub use super::spatial::*;
     pub use super::tone::*;
 }
@@ -0,0 +1,114 @@
+use crate::asset::AudioClip;
+use crate::buffer::AudioBufferMut;
+use crate::node::{AudioNode, NodeEvent, ProcessContext};
+
+// Plays an `AudioClip` from the start, sending `Looped` every time it wraps
+// around or finishing at its end. Clips in another format are resampled on
+// the fly.
+#[derive(Debug)]
+pub struct ClipPlayerNode {
+    clip: AudioClip,
+    looping: bool,
+    step: f64,
+    pos: f64,
+    pending: Option<NodeEvent>,
+}
+
+impl ClipPlayerNode {
+    pub fn new(clip: &AudioClip) -> Self {
+        Self {
+            clip: clip.clone(),
+            looping: false,
+            step: 1.0,
+            pos: 0.0,
+            pending: None,
+        }
+    }
+
+    pub fn looping(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+
+    fn sample(&self, pos: f64, channel: usize) -> f32 {
+        let (frame, frac) = (pos as usize, pos.fract() as f32);
+        let a = self.clip.sample(frame, channel);
+        let b = match frame + 1 {
+            next if self.looping && next >= self.clip.frames() => self.clip.sample(0, channel),
+            next => self.clip.sample(next, channel),
+        };
+
+        a + (b - a) * frac
+    }
+}
+
+impl AudioNode for ClipPlayerNode {
+    fn prepare(&mut self, sample_rate: u32, _max_block_size: usize, _channels: usize) {
+        self.step = self.clip.sample_rate() as f64 / sample_rate as f64;
+    }
+
+    fn reset(&mut self) {
+        self.pos = 0.0;
+        self.pending = None;
+    }
+
+    fn process(&mut self, _ctx: &ProcessContext, output: &mut AudioBufferMut) {
+        let frames = self.clip.frames() as f64;
+
+        for i in 0..output.frames() {
+            for ch in 0..output.channels() {
+                output.channel_mut(ch)[i] = if self.pos < frames {
+                    self.sample(self.pos, ch)
+                } else {
+                    0.0
+                };
+            }
+
+            self.pos += self.step;
+
+            if self.looping && self.pos >= frames && frames > 0.0 {
+                self.pos -= frames;
+                self.pending = Some(NodeEvent::Looped);
+            }
+        }
+    }
+
+    fn take_event(&mut self) -> Option<NodeEvent> {
+        self.pending.take()
+    }
+
+    fn is_finished(&self) -> bool {
+        !self.looping && self.pos >= self.clip.frames() as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AudioNode, ClipPlayerNode, NodeEvent, ProcessContext};
+    use crate::asset::AudioClip;
+    use crate::buffer::AudioBuffer;
+
+    #[test]
+    fn clips_play_once_or_loop() {
+        let clip = AudioClip::new(vec![1.0, 2.0, 3.0], 1000, 1);
+        let ctx = ProcessContext::default();
+        let mut buffer = AudioBuffer::new(2, 4);
+
+        let mut once = ClipPlayerNode::new(&clip);
+        once.prepare(1000, 4, 2);
+        once.process(&ctx, &mut buffer.as_mut());
+        assert_eq!(buffer.channel(0), &[1.0, 2.0, 3.0, 0.0]);
+        assert_eq!(buffer.channel(1), buffer.channel(0));
+        assert!(once.is_finished());
+        assert_eq!(once.take_event(), None);
+
+        let mut looped = ClipPlayerNode::new(&clip).looping();
+        looped.prepare(2000, 4, 2);
+        looped.process(&ctx, &mut buffer.as_mut());
+        assert_eq!(buffer.channel(0), &[1.0, 1.5, 2.0, 2.5]);
+        looped.process(&ctx, &mut buffer.as_mut());
+        assert_eq!(buffer.channel(0), &[3.0, 2.0, 1.0, 1.5]);
+        assert_eq!(looped.take_event(), Some(NodeEvent::Looped));
+        assert!(!looped.is_finished());
+    }
+}
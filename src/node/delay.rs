@@ -1,56 +1,158 @@
-use crate::engine::MAX_BUFFER_SIZE;
-use crate::node::AudioNode;
+use crate::buffer::AudioBufferMut;
+use crate::engine::DEFAULT_SAMPLE_RATE;
+use crate::node::param::{ParamId, ParamInfo, ParamUnit, SmoothedParam};
+use crate::node::{AudioNode, ProcessContext};
+
+// About a second, so delays set up with `new` can still be made longer.
+const DEFAULT_MAX_DELAY: usize = DEFAULT_SAMPLE_RATE as usize;
 
 #[derive(Debug)]
 pub struct DelayNode {
-    delay_samples: usize,
-    buffer: Vec<f32>,
+    delay: SmoothedParam,
+    max_delay: usize,
+    params: [ParamInfo; 1],
+    lines: Vec<Vec<f32>>,
     write_pos: usize,
 }
 
 impl DelayNode {
+    pub const DELAY: ParamId = ParamId(0);
+
     pub fn new(delay_samples: usize) -> Self {
+        Self::with_max_delay(delay_samples, DEFAULT_MAX_DELAY)
+    }
+
+    pub fn with_max_delay(delay_samples: usize, max_delay_samples: usize) -> Self {
+        let max_delay = max_delay_samples.max(delay_samples);
+
         Self {
-            delay_samples,
-            buffer: vec![0.0; delay_samples + MAX_BUFFER_SIZE],
+            delay: SmoothedParam::new(delay_samples as f32),
+            max_delay,
+            params: [ParamInfo::new(
+                Self::DELAY,
+                "Delay",
+                0.0,
+                max_delay as f32,
+                delay_samples as f32,
+                ParamUnit::Samples,
+            )],
+            // Allocated by `prepare`, once the channel count and block size are known.
+            lines: Vec::new(),
             write_pos: 0,
         }
     }
 }
 
 impl AudioNode for DelayNode {
-    fn process(&mut self, _sample_pos: u32, output: &mut [f32]) {
-        for sample in output.iter_mut() {
-            self.buffer[self.write_pos] = *sample;
+    fn prepare(&mut self, sample_rate: u32, max_block_size: usize, channels: usize) {
+        let len = self.max_delay + max_block_size;
+        self.delay.prepare(sample_rate);
 
-            let read_pos =
-                (self.write_pos + self.buffer.len() - self.delay_samples) % self.buffer.len();
+        if self.lines.len() != channels || self.lines.iter().any(|line| line.len() != len) {
+            self.lines = vec![vec![0.0; len]; channels];
+            self.write_pos = 0;
+        }
+    }
 
-            *sample = self.buffer[read_pos];
+    fn reset(&mut self) {
+        for line in &mut self.lines {
+            line.fill(0.0);
+        }
 
-            self.write_pos = (self.write_pos + 1) % self.buffer.len();
+        self.write_pos = 0;
+        self.delay.reset();
+    }
+
+    fn params(&self) -> &[ParamInfo] {
+        &self.params
+    }
+
+    fn set_param(&mut self, id: ParamId, value: f32) {
+        if id == Self::DELAY {
+            self.delay.set(value.clamp(0.0, self.max_delay as f32));
         }
     }
+
+    fn process(&mut self, _ctx: &ProcessContext, output: &mut AudioBufferMut) {
+        let (mut write_pos, delay) = (self.write_pos, self.delay);
+
+        for (channel, line) in output.channels_mut().zip(&mut self.lines) {
+            (write_pos, self.delay) = (self.write_pos, delay);
+            let len = line.len();
+
+            for sample in channel.iter_mut() {
+                line[write_pos] = *sample;
+
+                // Fractional read position, so delay changes glide instead of click.
+                let read_pos = (write_pos + len) as f32 - self.delay.tick();
+                let frac = read_pos.fract();
+                let a = read_pos as usize % len;
+                let b = (a + 1) % len;
+
+                *sample = line[a] + (line[b] - line[a]) * frac;
+
+                write_pos = (write_pos + 1) % len;
+            }
+        }
+
+        self.write_pos = write_pos;
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{AudioNode, DelayNode};
-    use crate::engine::SAMPLE_RATE;
+    use super::{AudioNode, DelayNode, ProcessContext};
+    use crate::buffer::{AudioBuffer, AudioBufferMut};
+    use crate::engine::DEFAULT_SAMPLE_RATE;
     use crate::node::nodes::ToneGeneratorNode;
     use crate::node::test_utils::test::*;
 
     #[test]
     fn plot_tone_generator() {
-        let freq = SAMPLE_RATE as f32 / 2048.0;
+        let freq = DEFAULT_SAMPLE_RATE as f32 / 2048.0;
         let mut tone = ToneGeneratorNode::new(freq * 2.0, 0.5);
         let mut delay = DelayNode::new(500);
+        delay.prepare(DEFAULT_SAMPLE_RATE, 2048, 1);
 
         let mut buffer = [0.0; 2048];
+        let mut output = AudioBufferMut::new(&mut buffer, 1, 2048);
 
-        tone.process(0, &mut buffer);
-        delay.process(0, &mut buffer);
+        tone.process(&ProcessContext::default(), &mut output);
+        delay.process(&ProcessContext::default(), &mut output);
 
         node_test_suite(&buffer, 1024, "delay");
     }
+
+    #[test]
+    fn channels_are_delayed_independently() {
+        let mut delay = DelayNode::new(2);
+        delay.prepare(DEFAULT_SAMPLE_RATE, 4, 2);
+
+        let mut buffer = AudioBuffer::new(2, 4);
+        {
+            let mut output = buffer.as_mut();
+            output.channel_mut(0)[0] = 1.0;
+            output.channel_mut(1)[1] = 1.0;
+            delay.process(&ProcessContext::default(), &mut output);
+        }
+
+        assert_eq!(buffer.channel(0), &[0.0, 0.0, 1.0, 0.0]);
+        assert_eq!(buffer.channel(1), &[0.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn delays_can_grow_past_their_initial_length() {
+        let mut delay = DelayNode::new(0);
+        assert!(delay.params()[0].max >= DEFAULT_SAMPLE_RATE as f32);
+
+        delay.prepare(DEFAULT_SAMPLE_RATE, 4, 1);
+        delay.set_param(DelayNode::DELAY, 2.0);
+        delay.reset();
+
+        let mut buffer = AudioBuffer::new(1, 4);
+        buffer.as_mut().channel_mut(0)[0] = 1.0;
+        delay.process(&ProcessContext::default(), &mut buffer.as_mut());
+
+        assert_eq!(buffer.channel(0), &[0.0, 0.0, 1.0, 0.0]);
+    }
 }
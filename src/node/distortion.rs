@@ -1,45 +1,104 @@
-use crate::node::AudioNode;
+use crate::buffer::AudioBufferMut;
+use crate::node::param::{ParamId, ParamInfo, ParamUnit, SmoothedParam};
+use crate::node::{AudioNode, ProcessContext};
 use std::f32::consts::PI;
 
 const SOFT_CLIP_NORM: f32 = 2.0 / PI;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub enum DistortionType {
     SoftClip,
     HardClip,
     SineWarp,
 }
 
+impl DistortionType {
+    fn from_param(value: f32) -> Self {
+        match value.round() as u32 {
+            0 => Self::SoftClip,
+            1 => Self::HardClip,
+            _ => Self::SineWarp,
+        }
+    }
+
+    fn to_param(self) -> f32 {
+        self as u32 as f32
+    }
+}
+
 #[derive(Debug)]
 pub struct DistortionNode {
-    gain: f32,
-    ceil: f32,
+    gain: SmoothedParam,
+    ceil: SmoothedParam,
     mode: DistortionType,
+    // Defaults follow what the node was built with.
+    params: [ParamInfo; 3],
 }
 
 impl DistortionNode {
+    pub const GAIN: ParamId = ParamId(0);
+    pub const CEIL: ParamId = ParamId(1);
+    pub const MODE: ParamId = ParamId(2);
+
     pub fn new(gain: f32, ceil: f32, mode: DistortionType) -> Self {
-        Self { gain, mode, ceil }
+        Self {
+            gain: SmoothedParam::new(gain),
+            ceil: SmoothedParam::new(ceil),
+            mode,
+            params: [
+                ParamInfo::new(Self::GAIN, "Gain", 0.0, 100.0, gain, ParamUnit::Gain),
+                ParamInfo::new(Self::CEIL, "Ceiling", 0.0, 1.0, ceil, ParamUnit::Gain),
+                ParamInfo::new(
+                    Self::MODE,
+                    "Mode",
+                    0.0,
+                    2.0,
+                    mode.to_param(),
+                    ParamUnit::Choice,
+                ),
+            ],
+        }
     }
 }
 
 impl AudioNode for DistortionNode {
-    fn process(&mut self, _sample_pos: u32, output: &mut [f32]) {
-        match self.mode {
-            DistortionType::SoftClip => {
-                for sample in output {
-                    *sample = SOFT_CLIP_NORM * (self.gain * *sample).atan() * self.ceil;
-                }
-            }
-            DistortionType::HardClip => {
-                for sample in output {
-                    *sample = (self.gain * *sample).clamp(-self.ceil, self.ceil);
-                }
-            }
-            DistortionType::SineWarp => {
-                for sample in output {
-                    *sample = (self.gain * *sample).sin() * self.ceil;
-                }
+    fn prepare(&mut self, sample_rate: u32, _max_block_size: usize, _channels: usize) {
+        self.gain.prepare(sample_rate);
+        self.ceil.prepare(sample_rate);
+    }
+
+    fn reset(&mut self) {
+        self.gain.reset();
+        self.ceil.reset();
+    }
+
+    fn params(&self) -> &[ParamInfo] {
+        &self.params
+    }
+
+    fn set_param(&mut self, id: ParamId, value: f32) {
+        match id {
+            Self::GAIN => self.gain.set(value),
+            Self::CEIL => self.ceil.set(value),
+            Self::MODE => self.mode = DistortionType::from_param(value),
+            _ => {}
+        }
+    }
+
+    fn process(&mut self, _ctx: &ProcessContext, output: &mut AudioBufferMut) {
+        let (gain, ceil) = (self.gain, self.ceil);
+
+        for channel in output.channels_mut() {
+            (self.gain, self.ceil) = (gain, ceil);
+
+            for sample in channel {
+                let (gain, ceil) = (self.gain.tick(), self.ceil.tick());
+
+                *sample = match self.mode {
+                    DistortionType::SoftClip => SOFT_CLIP_NORM * (gain * *sample).atan() * ceil,
+                    DistortionType::HardClip => (gain * *sample).clamp(-ceil, ceil),
+                    DistortionType::SineWarp => (gain * *sample).sin() * ceil,
+                };
             }
         }
     }
@@ -53,14 +112,15 @@ impl Default for DistortionNode {
 
 #[cfg(test)]
 mod test {
-    use super::{AudioNode, DistortionNode, DistortionType};
-    use crate::engine::SAMPLE_RATE;
+    use super::{AudioNode, DistortionNode, DistortionType, ProcessContext};
+    use crate::buffer::AudioBufferMut;
+    use crate::engine::DEFAULT_SAMPLE_RATE;
     use crate::node::nodes::ToneGeneratorNode;
     use crate::node::test_utils::test::*;
 
     #[test]
     fn plot_tone_generator() {
-        let freq = SAMPLE_RATE as f32 / 2048.0;
+        let freq = DEFAULT_SAMPLE_RATE as f32 / 2048.0;
         let mut tone = ToneGeneratorNode::new(freq * 2.0, 0.5);
         let mut dist = DistortionNode::new(10.0, 1.0, DistortionType::HardClip);
 
@@ -72,13 +132,22 @@ mod test {
 
         for (mode, name) in modes {
             let mut buffer = [0.0; 2048];
+            let mut output = AudioBufferMut::new(&mut buffer, 1, 2048);
 
             dist.mode = mode;
 
-            tone.process(0, &mut buffer);
-            dist.process(0, &mut buffer);
+            tone.process(&ProcessContext::default(), &mut output);
+            dist.process(&ProcessContext::default(), &mut output);
 
             node_test_suite(&buffer, 1024, &format!("dist-{name}"));
         }
     }
+
+    #[test]
+    fn params_default_to_the_constructor_values() {
+        let dist = DistortionNode::new(10.0, 0.5, DistortionType::SineWarp);
+        let defaults: Vec<_> = dist.params().iter().map(|info| info.default).collect();
+
+        assert_eq!(defaults, [10.0, 0.5, 2.0]);
+    }
 }
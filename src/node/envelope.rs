@@ -0,0 +1,98 @@
+use crate::buffer::AudioBufferMut;
+use crate::engine::DEFAULT_SAMPLE_RATE;
+use crate::node::{AudioNode, ProcessContext};
+use std::time::Duration;
+
+// Shapes its input with a linear attack, hold and release, then reports
+// itself finished so the engine removes it.
+#[derive(Debug)]
+pub struct EnvelopeNode {
+    attack: Duration,
+    hold: Duration,
+    release: Duration,
+    // In frames, at the prepared sample rate.
+    stages: [usize; 3],
+    pos: usize,
+}
+
+impl EnvelopeNode {
+    pub fn new(attack: Duration, hold: Duration, release: Duration) -> Self {
+        let mut node = Self {
+            attack,
+            hold,
+            release,
+            stages: [0; 3],
+            pos: 0,
+        };
+
+        node.set_sample_rate(DEFAULT_SAMPLE_RATE);
+        node
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        let frames = |stage: Duration| (stage.as_secs_f64() * sample_rate as f64) as usize;
+        self.stages = [frames(self.attack), frames(self.hold), frames(self.release)];
+    }
+
+    fn level(&self, pos: usize) -> f32 {
+        let [attack, hold, release] = self.stages;
+
+        if pos < attack {
+            pos as f32 / attack as f32
+        } else if pos < attack + hold {
+            1.0
+        } else if pos < attack + hold + release {
+            1.0 - (pos - attack - hold) as f32 / release as f32
+        } else {
+            0.0
+        }
+    }
+}
+
+impl AudioNode for EnvelopeNode {
+    fn prepare(&mut self, sample_rate: u32, _max_block_size: usize, _channels: usize) {
+        self.set_sample_rate(sample_rate);
+    }
+
+    fn reset(&mut self) {
+        self.pos = 0;
+    }
+
+    fn process(&mut self, _ctx: &ProcessContext, output: &mut AudioBufferMut) {
+        for channel in output.channels_mut() {
+            for (i, sample) in channel.iter_mut().enumerate() {
+                *sample *= self.level(self.pos + i);
+            }
+        }
+
+        self.pos += output.frames();
+    }
+
+    fn is_finished(&self) -> bool {
+        self.pos >= self.stages.iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AudioNode, EnvelopeNode, ProcessContext};
+    use crate::buffer::AudioBuffer;
+    use std::time::Duration;
+
+    #[test]
+    fn envelope_ramps_and_finishes() {
+        let ms = Duration::from_millis;
+        let mut node = EnvelopeNode::new(ms(2), ms(1), ms(2));
+        node.prepare(1000, 8, 1);
+
+        let mut buffer = AudioBuffer::new(1, 8);
+        buffer.as_mut().fill(1.0);
+        node.process(&ProcessContext::default(), &mut buffer.as_mut());
+
+        assert_eq!(buffer.channel(0), &[0.0, 0.5, 1.0, 1.0, 0.5, 0.0, 0.0, 0.0]);
+        assert!(node.is_finished());
+
+        node.reset();
+        assert!(!node.is_finished());
+    }
+}
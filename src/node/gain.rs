@@ -1,20 +1,59 @@
-use crate::node::AudioNode;
+use crate::buffer::AudioBufferMut;
+use crate::node::param::{ParamId, ParamInfo, ParamUnit, SmoothedParam};
+use crate::node::{AudioNode, ProcessContext};
+
+const PARAMS: [ParamInfo; 1] = [ParamInfo::new(
+    GainNode::GAIN,
+    "Gain",
+    0.0,
+    16.0,
+    1.0,
+    ParamUnit::Gain,
+)];
 
 #[derive(Debug)]
 pub struct GainNode {
-    gain: f32,
+    gain: SmoothedParam,
 }
 
 impl GainNode {
+    pub const GAIN: ParamId = ParamId(0);
+
     pub fn new(gain: f32) -> Self {
-        Self { gain }
+        Self {
+            gain: SmoothedParam::new(gain),
+        }
     }
 }
 
 impl AudioNode for GainNode {
-    fn process(&mut self, _sample_pos: u32, output: &mut [f32]) {
-        for sample in output {
-            *sample *= self.gain;
+    fn prepare(&mut self, sample_rate: u32, _max_block_size: usize, _channels: usize) {
+        self.gain.prepare(sample_rate);
+    }
+
+    fn reset(&mut self) {
+        self.gain.reset();
+    }
+
+    fn params(&self) -> &[ParamInfo] {
+        &PARAMS
+    }
+
+    fn set_param(&mut self, id: ParamId, value: f32) {
+        if id == Self::GAIN {
+            self.gain.set(value);
+        }
+    }
+
+    fn process(&mut self, _ctx: &ProcessContext, output: &mut AudioBufferMut) {
+        let gain = self.gain;
+
+        for channel in output.channels_mut() {
+            self.gain = gain;
+
+            for sample in channel {
+                *sample *= self.gain.tick();
+            }
         }
     }
 }
@@ -1,16 +1,22 @@
-use crate::{engine::MAX_BUFFER_SIZE, node::AudioNode};
+use crate::buffer::{AudioBuffer, AudioBufferMut};
+use crate::engine::{DEFAULT_CHANNELS, MAX_BUFFER_SIZE, NodeTimer};
+use crate::node::{AudioNode, NodeEvent, ProcessContext};
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub struct GroupNode {
-    buffer: [f32; MAX_BUFFER_SIZE],
+    buffer: AudioBuffer,
     nodes: Vec<Box<dyn AudioNode>>,
+    timers: Vec<Arc<NodeTimer>>,
 }
 
 impl GroupNode {
     pub fn new() -> Self {
         Self {
-            buffer: [0.0; MAX_BUFFER_SIZE],
+            buffer: AudioBuffer::new(DEFAULT_CHANNELS, MAX_BUFFER_SIZE),
             nodes: Vec::new(),
+            timers: Vec::new(),
         }
     }
 
@@ -19,21 +25,57 @@ impl GroupNode {
         A: AudioNode + 'static,
     {
         self.nodes.push(Box::new(node));
+        self.timers.push(Arc::default());
         self
     }
 }
 
 impl AudioNode for GroupNode {
-    fn process(&mut self, sample_pos: u32, output: &mut [f32]) {
-        self.buffer.fill(0.0);
+    fn prepare(&mut self, sample_rate: u32, max_block_size: usize, channels: usize) {
+        if self.buffer.channels() != channels || self.buffer.frames() != max_block_size {
+            self.buffer = AudioBuffer::new(channels, max_block_size);
+        }
+
+        for node in &mut self.nodes {
+            node.prepare(sample_rate, max_block_size, channels);
+        }
+    }
 
+    fn reset(&mut self) {
         for node in &mut self.nodes {
-            node.process(sample_pos, &mut self.buffer[0..output.len()]);
+            node.reset();
         }
+    }
 
-        for (i, sample) in output.iter_mut().enumerate() {
-            *sample += self.buffer[i];
+    fn process(&mut self, ctx: &ProcessContext, output: &mut AudioBufferMut) {
+        let mut buffer = self.buffer.slice_mut(0..output.frames());
+        buffer.fill(0.0);
+
+        for (node, timer) in self.nodes.iter_mut().zip(&self.timers) {
+            timer.measure(ctx.profiling, || node.process(ctx, &mut buffer));
         }
+
+        output.add_from(&buffer.as_ref());
+    }
+
+    // Members' events are reported as the group's own.
+    fn take_event(&mut self) -> Option<NodeEvent> {
+        self.nodes.iter_mut().find_map(|node| node.take_event())
+    }
+
+    // Done once every member is.
+    fn is_finished(&self) -> bool {
+        !self.nodes.is_empty() && self.nodes.iter().all(|node| node.is_finished())
+    }
+
+    fn tail(&self) -> Duration {
+        let tails = self.nodes.iter().map(|node| node.tail());
+        tails.max().unwrap_or_default()
+    }
+
+    fn child_timers(&self) -> Vec<(&'static str, Arc<NodeTimer>)> {
+        let names = self.nodes.iter().map(|node| node.name());
+        names.zip(self.timers.iter().cloned()).collect()
     }
 }
 
@@ -0,0 +1,214 @@
+use crate::buffer::AudioBufferMut;
+use crate::engine::MAX_BUFFER_SIZE;
+use crate::node::{AudioNode, ProcessContext};
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Single producer, single consumer ring of interleaved samples. Positions
+// only grow, `tail - head` is the number of readable samples.
+struct Ring {
+    data: Box<[UnsafeCell<f32>]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// The producer only writes between tail and head, the consumer only reads
+// between head and tail, `InputSender` and `InputNode` can't be cloned.
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    fn new(capacity: usize) -> Self {
+        Self {
+            data: (0..capacity).map(|_| UnsafeCell::new(0.0)).collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    fn slot(&self, pos: usize) -> *mut f32 {
+        self.data[pos % self.data.len()].get()
+    }
+}
+
+// Feeds an `InputNode`, either from an input stream or from anything else
+// producing interleaved samples, like a voice chat decoder.
+pub struct InputSender {
+    ring: Arc<Ring>,
+    channels: usize,
+}
+
+impl InputSender {
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    // Returns the number of samples written, whatever doesn't fit is dropped.
+    pub fn push(&mut self, samples: &[f32]) -> usize {
+        self.push_iter(samples.iter().copied(), samples.len())
+    }
+
+    pub(crate) fn push_iter(&mut self, samples: impl Iterator<Item = f32>, len: usize) -> usize {
+        let tail = self.ring.tail.load(Ordering::Relaxed);
+        let head = self.ring.head.load(Ordering::Acquire);
+        let free = self.ring.data.len() - (tail - head);
+
+        // Whole frames only, so channels never get shifted.
+        let count = len.min(free) / self.channels * self.channels;
+
+        for (i, sample) in samples.take(count).enumerate() {
+            unsafe { *self.ring.slot(tail + i) = sample };
+        }
+
+        self.ring.tail.store(tail + count, Ordering::Release);
+        count
+    }
+}
+
+impl fmt::Debug for InputSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InputSender")
+            .field("channels", &self.channels)
+            .finish_non_exhaustive()
+    }
+}
+
+// Plays back captured audio `latency` frames behind the sender. After an
+// underrun it waits for the buffer to fill up again, if the sender runs
+// ahead it skips to stay aligned.
+pub struct InputNode {
+    ring: Arc<Ring>,
+    channels: usize,
+    latency: usize,
+    primed: bool,
+    scratch: Vec<f32>,
+}
+
+impl InputNode {
+    pub fn new(channels: usize, latency: usize) -> (Self, InputSender) {
+        let channels = channels.max(1);
+        let ring = Arc::new(Ring::new((latency * 4 + MAX_BUFFER_SIZE) * channels));
+
+        let node = Self {
+            ring: ring.clone(),
+            channels,
+            latency,
+            primed: false,
+            scratch: vec![0.0; MAX_BUFFER_SIZE * channels],
+        };
+
+        (node, InputSender { ring, channels })
+    }
+
+    fn available(&self) -> usize {
+        let tail = self.ring.tail.load(Ordering::Acquire);
+        let head = self.ring.head.load(Ordering::Relaxed);
+        (tail - head) / self.channels
+    }
+
+    fn skip(&self, frames: usize) {
+        let head = self.ring.head.load(Ordering::Relaxed);
+        self.ring
+            .head
+            .store(head + frames * self.channels, Ordering::Release);
+    }
+
+    fn read(&mut self, frames: usize) {
+        let head = self.ring.head.load(Ordering::Relaxed);
+        let len = frames * self.channels;
+
+        for (i, sample) in self.scratch[..len].iter_mut().enumerate() {
+            *sample = unsafe { *self.ring.slot(head + i) };
+        }
+
+        self.ring.head.store(head + len, Ordering::Release);
+    }
+}
+
+impl AudioNode for InputNode {
+    fn prepare(&mut self, _sample_rate: u32, max_block_size: usize, _channels: usize) {
+        self.scratch.resize(max_block_size * self.channels, 0.0);
+    }
+
+    fn reset(&mut self) {
+        self.skip(self.available());
+        self.primed = false;
+    }
+
+    fn process(&mut self, _ctx: &ProcessContext, output: &mut AudioBufferMut) {
+        let frames = output.frames();
+        let available = self.available();
+
+        if !self.primed && available >= self.latency + frames {
+            self.primed = true;
+        }
+
+        if self.primed && available > self.latency * 2 + frames {
+            self.skip(available - self.latency - frames);
+        }
+
+        let read = if self.primed {
+            frames.min(available)
+        } else {
+            0
+        };
+
+        if read < frames {
+            self.primed = false;
+        }
+
+        self.read(read);
+
+        for (ch, channel) in output.channels_mut().enumerate() {
+            let src = ch % self.channels;
+
+            for (i, sample) in channel[..read].iter_mut().enumerate() {
+                *sample += self.scratch[i * self.channels + src];
+            }
+        }
+    }
+}
+
+impl fmt::Debug for InputNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InputNode")
+            .field("channels", &self.channels)
+            .field("latency", &self.latency)
+            .field("primed", &self.primed)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AudioNode, InputNode, ProcessContext};
+    use crate::buffer::AudioBuffer;
+
+    #[test]
+    fn input_waits_for_latency_and_keeps_order() {
+        let (mut node, mut sender) = InputNode::new(1, 64);
+        let mut output = AudioBuffer::new(2, 32);
+        let ctx = ProcessContext::default();
+
+        let ramp: Vec<f32> = (1..=128).map(|i| i as f32).collect();
+        assert_eq!(sender.push(&ramp[..64]), 64);
+
+        // Not enough for the latency plus a block yet.
+        node.process(&ctx, &mut output.as_mut());
+        assert!(output.channel(0).iter().all(|s| *s == 0.0));
+
+        sender.push(&ramp[64..]);
+        node.process(&ctx, &mut output.as_mut());
+
+        assert_eq!(output.channel(0), &ramp[..32]);
+        assert_eq!(output.channel(1), &ramp[..32]);
+    }
+
+    #[test]
+    fn partial_frames_are_not_written() {
+        let (_, mut sender) = InputNode::new(2, 16);
+
+        assert_eq!(sender.push(&[1.0, 2.0, 3.0]), 2);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::engine::DEFAULT_SAMPLE_RATE;
+
+const SMOOTHING_SECONDS: f32 = 0.02;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ParamId(pub u32);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ParamUnit {
+    Generic,
+    Gain,
+    Hertz,
+    Samples,
+    Seconds,
+    Choice,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParamInfo {
+    pub id: ParamId,
+    pub name: &'static str,
+    pub min: f32,
+    pub max: f32,
+    pub default: f32,
+    pub unit: ParamUnit,
+}
+
+impl ParamInfo {
+    pub const fn new(
+        id: ParamId,
+        name: &'static str,
+        min: f32,
+        max: f32,
+        default: f32,
+        unit: ParamUnit,
+    ) -> Self {
+        Self {
+            id,
+            name,
+            min,
+            max,
+            default,
+            unit,
+        }
+    }
+
+    pub fn clamp(&self, value: f32) -> f32 {
+        value.clamp(self.min, self.max)
+    }
+}
+
+// Linear ramp towards the last target, advanced once per frame.
+#[derive(Clone, Copy, Debug)]
+pub struct SmoothedParam {
+    current: f32,
+    target: f32,
+    step: f32,
+    remaining: u32,
+    ramp: u32,
+}
+
+impl SmoothedParam {
+    pub fn new(value: f32) -> Self {
+        Self {
+            current: value,
+            target: value,
+            step: 0.0,
+            remaining: 0,
+            ramp: ramp_len(DEFAULT_SAMPLE_RATE),
+        }
+    }
+
+    pub fn prepare(&mut self, sample_rate: u32) {
+        self.ramp = ramp_len(sample_rate);
+    }
+
+    pub fn set(&mut self, target: f32) {
+        self.target = target;
+        self.remaining = self.ramp;
+        self.step = (target - self.current) / self.ramp as f32;
+    }
+
+    pub fn jump(&mut self, value: f32) {
+        self.current = value;
+        self.target = value;
+        self.remaining = 0;
+    }
+
+    pub fn reset(&mut self) {
+        self.jump(self.target);
+    }
+
+    pub fn value(&self) -> f32 {
+        self.current
+    }
+
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    pub fn is_smoothing(&self) -> bool {
+        self.remaining > 0
+    }
+
+    #[inline]
+    pub fn tick(&mut self) -> f32 {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            self.current = if self.remaining == 0 {
+                self.target
+            } else {
+                self.current + self.step
+            };
+        }
+
+        self.current
+    }
+}
+
+fn ramp_len(sample_rate: u32) -> u32 {
+    ((sample_rate as f32 * SMOOTHING_SECONDS) as u32).max(1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::SmoothedParam;
+
+    #[test]
+    fn ramps_to_target() {
+        let mut param = SmoothedParam::new(0.0);
+        param.prepare(1000);
+        param.set(1.0);
+
+        let ramp: Vec<f32> = (0..20).map(|_| param.tick()).collect();
+
+        assert!(ramp.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(ramp[19], 1.0);
+        assert!(!param.is_smoothing());
+        assert_eq!(param.tick(), 1.0);
+    }
+}
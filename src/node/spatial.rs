@@ -0,0 +1,158 @@
+use crate::buffer::AudioBufferMut;
+use crate::engine::DEFAULT_SAMPLE_RATE;
+use crate::node::param::{ParamId, ParamInfo, ParamUnit, SmoothedParam};
+use crate::node::{AudioNode, ProcessContext};
+use std::f32::consts::FRAC_PI_4;
+
+// About 340 meters worth of propagation delay.
+const MAX_DELAY_SECONDS: f32 = 1.0;
+
+const PARAMS: [ParamInfo; 3] = [
+    ParamInfo::new(SpatialNode::GAIN, "Gain", 0.0, 1.0, 0.0, ParamUnit::Gain),
+    ParamInfo::new(SpatialNode::PAN, "Pan", -1.0, 1.0, 0.0, ParamUnit::Generic),
+    ParamInfo::new(
+        SpatialNode::DELAY,
+        "Delay",
+        0.0,
+        MAX_DELAY_SECONDS,
+        0.0,
+        ParamUnit::Seconds,
+    ),
+];
+
+// Mixes its input down to mono and places it between the outputs. `DELAY` is
+// the propagation delay, changing it smoothly gives the Doppler shift.
+#[derive(Debug)]
+pub struct SpatialNode {
+    gain: SmoothedParam,
+    pan: SmoothedParam,
+    delay: SmoothedParam,
+    sample_rate: f32,
+    line: Vec<f32>,
+    write_pos: usize,
+}
+
+impl SpatialNode {
+    pub const GAIN: ParamId = ParamId(0);
+    pub const PAN: ParamId = ParamId(1);
+    pub const DELAY: ParamId = ParamId(2);
+
+    pub fn new() -> Self {
+        Self {
+            // Silent until the first update, so far away emitters don't pop in.
+            gain: SmoothedParam::new(0.0),
+            pan: SmoothedParam::new(0.0),
+            delay: SmoothedParam::new(0.0),
+            sample_rate: DEFAULT_SAMPLE_RATE as f32,
+            // Allocated by `prepare`, once the sample rate and block size are known.
+            line: Vec::new(),
+            write_pos: 0,
+        }
+    }
+}
+
+impl AudioNode for SpatialNode {
+    fn prepare(&mut self, sample_rate: u32, max_block_size: usize, _channels: usize) {
+        let len = (sample_rate as f32 * MAX_DELAY_SECONDS) as usize + max_block_size;
+
+        self.gain.prepare(sample_rate);
+        self.pan.prepare(sample_rate);
+        self.delay.prepare(sample_rate);
+        self.sample_rate = sample_rate as f32;
+
+        if self.line.len() != len {
+            self.line = vec![0.0; len];
+            self.write_pos = 0;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.line.fill(0.0);
+        self.write_pos = 0;
+        self.gain.reset();
+        self.pan.reset();
+        self.delay.reset();
+    }
+
+    fn params(&self) -> &[ParamInfo] {
+        &PARAMS
+    }
+
+    fn set_param(&mut self, id: ParamId, value: f32) {
+        match id {
+            Self::GAIN => self.gain.set(value),
+            Self::PAN => self.pan.set(value),
+            Self::DELAY => self.delay.set(value),
+            _ => {}
+        }
+    }
+
+    fn process(&mut self, _ctx: &ProcessContext, output: &mut AudioBufferMut) {
+        let channels = output.channels();
+        let len = self.line.len();
+
+        if len == 0 {
+            return;
+        }
+
+        for i in 0..output.frames() {
+            let sum: f32 = (0..channels).map(|ch| output.channel(ch)[i]).sum();
+            self.line[self.write_pos] = sum / channels as f32;
+
+            let read_pos = (self.write_pos + len) as f32 - self.delay.tick() * self.sample_rate;
+            let frac = read_pos.fract();
+            let a = read_pos as usize % len;
+            let b = (a + 1) % len;
+            let delayed = self.line[a] + (self.line[b] - self.line[a]) * frac;
+            let sample = delayed * self.gain.tick();
+
+            self.write_pos = (self.write_pos + 1) % len;
+
+            // Equal power, even channels are on the left and odd ones on the right.
+            let angle = (self.pan.tick() + 1.0) * FRAC_PI_4;
+            let sides = [angle.cos(), angle.sin()];
+
+            for ch in 0..channels {
+                let side = if channels == 1 { 1.0 } else { sides[ch % 2] };
+                output.channel_mut(ch)[i] = sample * side;
+            }
+        }
+    }
+}
+
+impl Default for SpatialNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AudioNode, ProcessContext, SpatialNode};
+    use crate::buffer::AudioBuffer;
+
+    #[test]
+    fn pan_and_delay_move_the_signal() {
+        let mut node = SpatialNode::new();
+        let params = node.params().to_vec();
+        node.set_param(SpatialNode::GAIN, 1.0);
+        node.set_param(SpatialNode::PAN, 1.0);
+        node.set_param(SpatialNode::DELAY, 2.0 / 1024.0);
+
+        // The same delay in seconds lands further out at a higher rate.
+        for (sample_rate, at) in [(1024, 2), (2048, 4)] {
+            node.prepare(sample_rate, 8, 2);
+            node.reset();
+            assert_eq!(node.params(), params);
+
+            let mut buffer = AudioBuffer::new(2, 8);
+            buffer.as_mut().channel_mut(0)[0] = 1.0;
+            buffer.as_mut().channel_mut(1)[0] = 1.0;
+            node.process(&ProcessContext::default(), &mut buffer.as_mut());
+
+            assert!(buffer.channel(0).iter().all(|s| s.abs() < 1e-6));
+            assert_eq!(buffer.channel(1)[at], 1.0);
+            assert_eq!(buffer.channel(1).iter().sum::<f32>(), 1.0);
+        }
+    }
+}
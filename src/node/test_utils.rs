@@ -1,6 +1,6 @@
 #[cfg(test)]
 pub mod test {
-    use crate::engine::SAMPLE_RATE;
+    use crate::engine::DEFAULT_SAMPLE_RATE;
     use hound;
     use plotters::prelude::*;
     use rustfft::{FftPlanner, num_complex::Complex};
@@ -26,7 +26,7 @@ pub mod test {
     ) -> Result<(), Box<dyn std::error::Error>> {
         let spec = hound::WavSpec {
             channels: 1,
-            sample_rate: SAMPLE_RATE,
+            sample_rate: DEFAULT_SAMPLE_RATE,
             bits_per_sample: 16,
             sample_format: hound::SampleFormat::Int,
         };
@@ -47,7 +47,7 @@ pub mod test {
         root.fill(&WHITE).unwrap();
 
         let len = samples.len();
-        let duration = len as f32 / SAMPLE_RATE as f32;
+        let duration = len as f32 / DEFAULT_SAMPLE_RATE as f32;
 
         let mut chart = ChartBuilder::on(&root)
             .margin(10)
@@ -65,7 +65,7 @@ pub mod test {
 
         chart
             .draw_series(LineSeries::new(
-                (0..len).map(|i| (i as f32 / SAMPLE_RATE as f32, samples[i])),
+                (0..len).map(|i| (i as f32 / DEFAULT_SAMPLE_RATE as f32, samples[i])),
                 &BLACK,
             ))
             .unwrap();
@@ -114,7 +114,7 @@ pub mod test {
         let height = spec[0].len();
 
         let f_min = 20.0;
-        let f_max = SAMPLE_RATE as f32 / 2.0;
+        let f_max = DEFAULT_SAMPLE_RATE as f32 / 2.0;
         let octaves = (f_max / f_min).log2();
 
         let image_width = width as u32 + 100;
@@ -153,7 +153,7 @@ pub mod test {
                 let f_y = f_min * 2f32.powf(y_norm * octaves);
                 let ln_f_y = f_y.ln();
 
-                let b_y = f_y * window_size as f32 / SAMPLE_RATE as f32;
+                let b_y = f_y * window_size as f32 / DEFAULT_SAMPLE_RATE as f32;
                 let val = interp_linear(column, b_y);
 
                 let intensity = (val - min_val) / (max_val - min_val);
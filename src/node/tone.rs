@@ -1,32 +1,89 @@
-use crate::{engine::SAMPLE_RATE, node::AudioNode};
+use crate::buffer::AudioBufferMut;
+use crate::engine::DEFAULT_SAMPLE_RATE;
+use crate::node::param::{ParamId, ParamInfo, ParamUnit, SmoothedParam};
+use crate::node::{AudioNode, ProcessContext};
 use std::f32::consts::TAU;
 
+const PARAMS: [ParamInfo; 2] = [
+    ParamInfo::new(
+        ToneGeneratorNode::FREQUENCY,
+        "Frequency",
+        0.0,
+        20_000.0,
+        440.0,
+        ParamUnit::Hertz,
+    ),
+    ParamInfo::new(
+        ToneGeneratorNode::VOLUME,
+        "Volume",
+        0.0,
+        1.0,
+        1.0,
+        ParamUnit::Gain,
+    ),
+];
+
 #[derive(Debug)]
 pub struct ToneGeneratorNode {
-    volume: f32,
+    freq: SmoothedParam,
+    volume: SmoothedParam,
+    sample_rate: u32,
     phase: f32,
-    phase_inc: f32,
 }
 
 impl ToneGeneratorNode {
+    pub const FREQUENCY: ParamId = ParamId(0);
+    pub const VOLUME: ParamId = ParamId(1);
+
     pub fn new<N: Into<f32>>(freq: N, volume: f32) -> Self {
-        let phase_inc = (freq.into() / SAMPLE_RATE as f32) * TAU;
         Self {
-            volume,
+            freq: SmoothedParam::new(freq.into()),
+            volume: SmoothedParam::new(volume),
+            sample_rate: DEFAULT_SAMPLE_RATE,
             phase: 0.0,
-            phase_inc,
         }
     }
 }
 
 impl AudioNode for ToneGeneratorNode {
-    fn process(&mut self, _sample_pos: u32, output: &mut [f32]) {
-        for sample in output.iter_mut() {
-            *sample += self.phase.sin() * self.volume;
-            self.phase += self.phase_inc;
+    fn prepare(&mut self, sample_rate: u32, _max_block_size: usize, _channels: usize) {
+        self.sample_rate = sample_rate;
+        self.freq.prepare(sample_rate);
+        self.volume.prepare(sample_rate);
+    }
+
+    fn reset(&mut self) {
+        self.phase = 0.0;
+        self.freq.reset();
+        self.volume.reset();
+    }
+
+    fn params(&self) -> &[ParamInfo] {
+        &PARAMS
+    }
+
+    fn set_param(&mut self, id: ParamId, value: f32) {
+        match id {
+            Self::FREQUENCY => self.freq.set(value),
+            Self::VOLUME => self.volume.set(value),
+            _ => {}
+        }
+    }
 
-            if self.phase > TAU {
-                self.phase -= TAU;
+    fn process(&mut self, _ctx: &ProcessContext, output: &mut AudioBufferMut) {
+        let (phase, freq, volume) = (self.phase, self.freq, self.volume);
+        let to_phase_inc = TAU / self.sample_rate as f32;
+
+        for channel in output.channels_mut() {
+            (self.phase, self.freq, self.volume) = (phase, freq, volume);
+
+            for sample in channel {
+                *sample += self.phase.sin() * self.volume.tick();
+                self.phase += self.freq.tick() * to_phase_inc;
+
+                if self.phase > TAU {
+                    self.phase -= TAU;
+                }
             }
         }
     }
@@ -34,21 +91,38 @@ impl AudioNode for ToneGeneratorNode {
 
 #[cfg(test)]
 mod test {
-    use super::{AudioNode, ToneGeneratorNode};
-    use crate::engine::SAMPLE_RATE;
+    use super::{AudioNode, ProcessContext, TAU, ToneGeneratorNode};
+    use crate::buffer::{AudioBuffer, AudioBufferMut};
+    use crate::engine::DEFAULT_SAMPLE_RATE;
     use crate::node::test_utils::test::*;
 
     #[test]
     fn plot_tone_generator() {
-        let freq = SAMPLE_RATE as f32 / 2048.0;
+        let freq = DEFAULT_SAMPLE_RATE as f32 / 2048.0;
         let mut tone1 = ToneGeneratorNode::new(freq * 2.0, 0.5);
         let mut tone2 = ToneGeneratorNode::new(freq * 3.0, 0.5);
 
         let mut buffer = [0.0; 2048];
+        let mut output = AudioBufferMut::new(&mut buffer, 1, 2048);
 
-        tone1.process(0, &mut buffer);
-        tone2.process(0, &mut buffer);
+        tone1.process(&ProcessContext::default(), &mut output);
+        tone2.process(&ProcessContext::default(), &mut output);
 
         node_test_suite(&buffer, 1024, "tone-generator");
     }
+
+    #[test]
+    fn prepare_follows_sample_rate() {
+        let mut tone = ToneGeneratorNode::new(1000.0_f32, 1.0);
+        tone.prepare(48_000, 64, 2);
+
+        let mut buffer = AudioBuffer::new(2, 48);
+        tone.process(&ProcessContext::default(), &mut buffer.as_mut());
+
+        assert!(tone.phase < 1e-3 || (TAU - tone.phase) < 1e-3);
+        assert_eq!(buffer.channel(0), buffer.channel(1));
+
+        tone.reset();
+        assert_eq!(tone.phase, 0.0);
+    }
 }